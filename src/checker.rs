@@ -4,36 +4,48 @@ use futures::future::join_all;
 use std::str::from_utf8;
 use tokio::process::{Child, Command};
 use tokio::spawn;
+use tokio_util::sync::CancellationToken;
 
 use crate::fetch::ProblemIO;
-use crate::{fetch, RECURSE_DEPTH};
-use anyhow::{anyhow, bail, Result};
+use crate::gitignore::Gitignore;
+use crate::{fetch, host, interrupt, link, progress, use_color, RECURSE_DEPTH};
+use anyhow::{anyhow, bail, Context, Result};
 use colored::Colorize;
 use futures::prelude::stream::*;
 use futures::stream::TryStreamExt;
 
 use regex::Regex;
+use std::collections::HashSet;
+use std::ffi::OsStr;
 use std::fmt;
-use std::fmt::Formatter;
+use std::fmt::{Formatter, Write as _};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::io::Write as _;
 use std::path::{Path, PathBuf};
-use std::process::{Output, Stdio};
+use std::process::{ExitStatus, Output, Stdio};
+use std::sync::LazyLock;
 
-use crate::compare::{compare, ComparisonResult};
+use crate::compare::{compare, CompareOptions, ComparisonResult};
 use crate::submit::submit;
+use clap::builder::PossibleValue;
+use clap::ValueEnum;
 use enum_iterator::{all, Sequence};
 use futures::executor::block_on;
 use itertools::Itertools;
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
 use tokio::io::AsyncReadExt;
 
 #[cfg(unix)]
 use std::os::unix::process::ExitStatusExt;
 
-use log::info;
-use std::time::SystemTime;
+use crate::submit::viewer::{PollOptions, SubmissionViewerType};
+use log::{info, warn};
+use std::time::{Duration, Instant, SystemTime};
 use walkdir::DirEntry;
-use crate::submit::viewer::SubmissionViewerType;
 
 #[derive(Debug)]
+#[allow(clippy::struct_field_names)]
 pub struct Problem {
     pub problem_name: String,
     pub submission: Program,
@@ -52,16 +64,247 @@ impl Problem {
         self.submit = submit;
         self
     }
+    pub const fn set_use_cache(mut self, use_cache: bool) -> Self {
+        self.submission.use_cache = use_cache;
+        self
+    }
+    pub const fn set_keep_binary(mut self, keep_binary: bool) -> Self {
+        self.submission.keep_binary = keep_binary;
+        self
+    }
+    pub const fn set_show_warnings(mut self, show_warnings: bool) -> Self {
+        self.submission.show_warnings = show_warnings;
+        self
+    }
+    pub const fn set_python_impl(mut self, python_impl: PythonImpl) -> Self {
+        self.submission.python_impl = python_impl;
+        self
+    }
+    pub const fn set_cpp_compiler(mut self, cpp_compiler: CppCompiler) -> Self {
+        self.submission.cpp_compiler = cpp_compiler;
+        self
+    }
+    pub fn set_cpp_bin(mut self, cpp_bin: Option<String>) -> Self {
+        self.submission.cpp_bin = cpp_bin;
+        self
+    }
+    pub fn set_rustc_bin(mut self, rustc_bin: Option<String>) -> Self {
+        self.submission.rustc_bin = rustc_bin;
+        self
+    }
+    pub fn set_extra_flags(mut self, extra_flags: Vec<String>) -> Self {
+        self.submission.extra_flags = extra_flags;
+        self
+    }
+
+    pub fn set_extra_sources(mut self, extra_sources: Vec<PathBuf>) -> Self {
+        self.submission.extra_sources = extra_sources;
+        self
+    }
+}
+
+/// The Python implementation used to run Python programs locally
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PythonImpl {
+    #[default]
+    CPython,
+    PyPy,
+}
+
+impl ValueEnum for PythonImpl {
+    fn value_variants<'a>() -> &'a [Self] {
+        &[Self::CPython, Self::PyPy]
+    }
+
+    fn to_possible_value(&self) -> Option<PossibleValue> {
+        Some(match self {
+            Self::CPython => PossibleValue::new("cpython").help("Run with python3"),
+            Self::PyPy => PossibleValue::new("pypy").help("Run with pypy3"),
+        })
+    }
+}
+
+/// The compiler used for C++ programs locally. Kattis itself uses g++.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CppCompiler {
+    #[default]
+    Gcc,
+    Clang,
+}
+
+impl CppCompiler {
+    pub const fn command(self) -> &'static str {
+        match self {
+            Self::Gcc => "g++",
+            Self::Clang => "clang++",
+        }
+    }
+}
+
+impl ValueEnum for CppCompiler {
+    fn value_variants<'a>() -> &'a [Self] {
+        &[Self::Gcc, Self::Clang]
+    }
+
+    fn to_possible_value(&self) -> Option<PossibleValue> {
+        Some(match self {
+            Self::Gcc => PossibleValue::new("g++").help("GNU g++, like Kattis"),
+            Self::Clang => PossibleValue::new("clang++").help("LLVM clang++"),
+        })
+    }
+}
+
+/// Settings that apply to every problem being checked
+#[derive(Debug, Clone)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct CheckOptions {
+    pub force: bool,
+    pub submission_viewer: SubmissionViewerType,
+    pub poll: PollOptions,
+    /// Print the submission instead of sending it
+    pub dry_run: bool,
+    /// Submit without fetching the samples or running the program
+    pub submit_only: bool,
+    /// Warn about source code that is likely to fail on Kattis
+    pub lint: bool,
+    pub timeout: Duration,
+    /// Address space limit in megabytes, or the heap limit for the JVM and Mono. Only enforced on
+    /// Unix.
+    pub mem_limit: Option<u64>,
+    /// Directory with extra `.in`/`.ans` test cases to run alongside the samples
+    pub test_dir: Option<PathBuf>,
+    /// Input and answer of a single test case that is run instead of the samples
+    pub single_case: Option<(PathBuf, PathBuf)>,
+    /// Names of the test cases to run, or empty to run all of them
+    pub cases: Vec<String>,
+    pub compare: CompareOptions,
+    /// Program that judges the output instead of comparing it with the expected output
+    pub checker: Option<PathBuf>,
+    /// Judge that the program talks to over stdin and stdout, for interactive problems
+    pub interactive: Option<PathBuf>,
+    /// Cached samples older than this are downloaded again
+    pub sample_max_age: Option<Duration>,
+    pub output: OutputFormat,
+    /// Only print the problems and cases that failed
+    pub quiet: bool,
+    /// Number of test cases of a problem that are run at the same time
+    pub jobs: usize,
+    /// Number of times each case is run to measure its runtime
+    pub repeat: usize,
+    /// Print the output of the programs while they run
+    pub stream: bool,
+    /// Print up to this many lines of the input of failed cases
+    pub show_input: Option<usize>,
+    /// Ask before submitting each problem
+    pub confirm: bool,
+    /// Stop checking the other problems once one of them fails
+    pub fail_fast: bool,
+    /// Print one line per problem, followed by the cases only when it failed
+    pub compact: bool,
+}
+
+/// How checking a problem went
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    Passed,
+    Failed,
+    CompileError,
+    /// Submitted with --submit-only, without running it locally
+    Submitted,
+    /// Stopped by --fail-fast since another problem failed
+    Cancelled,
+    /// There were no test cases to run, so nothing was checked
+    NoSamples,
+}
+
+impl Outcome {
+    /// The outcome of a problem whose program ran on `cases`
+    const fn of_cases(cases: &[CaseRun], failed_any: bool) -> Self {
+        if cases.is_empty() {
+            Self::NoSamples
+        } else if failed_any {
+            Self::Failed
+        } else {
+            Self::Passed
+        }
+    }
+}
+
+/// Everything that was found out while checking a problem
+#[derive(Serialize)]
+pub struct ProblemReport {
+    pub problem: String,
+    pub program: String,
+    pub outcome: Outcome,
+    pub compile_error: Option<String>,
+    pub cases: Vec<CaseRun>,
+}
+
+impl ProblemReport {
+    fn cancelled(problem: &Problem) -> Self {
+        Self {
+            problem: problem.problem_name.clone(),
+            program: problem.submission.name().to_string(),
+            outcome: Outcome::Cancelled,
+            compile_error: None,
+            cases: Vec::new(),
+        }
+    }
+
+    /// Whether the program crashed on any of the cases
+    pub fn crashed(&self) -> bool {
+        self.cases
+            .iter()
+            .any(|case| matches!(case.run_result, RunResult::RuntimeError(..)))
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Colored text for reading in a terminal
+    #[default]
+    Human,
+    /// A JSON array with a report for every problem
+    Json,
+}
+
+impl ValueEnum for OutputFormat {
+    fn value_variants<'a>() -> &'a [Self] {
+        &[Self::Human, Self::Json]
+    }
+
+    fn to_possible_value(&self) -> Option<PossibleValue> {
+        Some(match self {
+            Self::Human => {
+                PossibleValue::new("human").help("Colored text for reading in a terminal")
+            }
+            Self::Json => {
+                PossibleValue::new("json").help("A JSON array with a report for every problem")
+            }
+        })
+    }
 }
 
 pub async fn check_problems(
     problems: Vec<Problem>,
-    force: bool,
-    submission_viewer: SubmissionViewerType,
-) -> Vec<(Problem, Result<bool>)> {
+    options: CheckOptions,
+) -> Vec<(Problem, Result<ProblemReport>)> {
+    let cancel = CancellationToken::new();
     let handles = problems.into_iter().map(|mut prob| {
+        let options = options.clone();
+        let cancel = cancel.clone();
         spawn(async move {
-            let checked = check_problem(&mut prob, force, submission_viewer).await;
+            let checked = check_problem(&mut prob, &options, &cancel).await;
+            let passed = checked.as_ref().is_ok_and(|report| {
+                matches!(
+                    report.outcome,
+                    Outcome::Passed | Outcome::Submitted | Outcome::Cancelled
+                )
+            });
+            if options.fail_fast && !passed {
+                cancel.cancel();
+            }
             (prob, checked)
         })
     });
@@ -85,16 +328,33 @@ pub struct Program {
     source: PathBuf,
     binary: Option<PathBuf>,
     compiled: Option<std::result::Result<(), String>>, // None if not compiled, Err if compile error
+    use_cache: bool,
+    cache_key: Option<String>, // Set while compiling when the binary cache is in use
+    keep_binary: bool,
+    show_warnings: bool,
+    interpreter: Option<String>, // Chosen while compiling for interpreted languages
+    python_impl: PythonImpl,
+    cpp_compiler: CppCompiler,
+    cpp_bin: Option<String>, // Replaces the executable of cpp_compiler, e.g. g++-13
+    rustc_bin: Option<String>,
+    extra_flags: Vec<String>, // From the config, added to the compiler invocation
+    extra_sources: Vec<PathBuf>, // Submitted along with the source, e.g. headers or helper files
 }
 
 impl Drop for Program {
     fn drop(&mut self) {
-        if let (true, Some(path)) = (&self.lang.compiled(), &self.binary) {
+        let (true, Some(path)) = (self.lang.compiled(), &self.binary) else {
+            return;
+        };
+        if self.keep_binary {
+            eprintln!("Kept binary for {} at {}", self.name(), path.display());
+        } else if self.cache_key.is_none() {
+            // Binaries in the cache are reused by later runs, so only remove uncached ones
             std::fs::remove_file(path).unwrap_or_else(|_| {
                 eprintln!(
-                    "[Warning] Failed to remove binary for {} at {:?}",
+                    "[Warning] Failed to remove binary for {} at {}",
                     self.name(),
-                    path
+                    path.display()
                 );
             });
         }
@@ -113,9 +373,32 @@ impl Program {
             source: problem_source.path,
             binary: None,
             compiled: None,
+            use_cache: true,
+            cache_key: None,
+            keep_binary: false,
+            show_warnings: false,
+            interpreter: None,
+            python_impl: PythonImpl::CPython,
+            cpp_compiler: CppCompiler::Gcc,
+            cpp_bin: None,
+            rustc_bin: None,
+            extra_flags: Vec::new(),
+            extra_sources: Vec::new(),
         }
     }
 
+    /// The executable that compiles C++
+    fn cpp_command(&self) -> &str {
+        self.cpp_bin
+            .as_deref()
+            .unwrap_or_else(|| self.cpp_compiler.command())
+    }
+
+    /// The executable that compiles Rust sources outside of Cargo projects
+    fn rustc_command(&self) -> &str {
+        self.rustc_bin.as_deref().unwrap_or("rustc")
+    }
+
     // pub fn new(path: PathBuf) -> Result<Self> {
     //     Ok(Self {
     //         lang: {
@@ -138,72 +421,338 @@ impl Program {
     //     })
     // }
 
+    /// Path in the temp directory that the compiled binary is written to
+    fn binary_path(&self) -> PathBuf {
+        let mut output_path = fetch::kattis_dir();
+        if let Some(key) = &self.cache_key {
+            output_path.push("bincache");
+            output_path.push(key);
+        } else {
+            output_path.push(format!(
+                "{}-{}",
+                self.lang.extension(),
+                self.source.file_stem().unwrap().to_str().unwrap()
+            ));
+        }
+        if self.lang == Lang::Kotlin {
+            output_path.as_mut_os_string().push(".jar"); // kotlinc only bundles into a jar given this suffix
+        } else if self.lang == Lang::CSharp {
+            output_path.as_mut_os_string().push(".exe"); // mono refuses to run assemblies without it
+        }
+        output_path
+    }
+
+    /// Hash of everything that affects the compiled binary: the source and how it is compiled
+    async fn compute_cache_key(&self) -> Result<String> {
+        let mut hasher = DefaultHasher::new();
+        hasher.write(&tokio::fs::read(&self.source).await?);
+        for extra in &self.extra_sources {
+            hasher.write(&tokio::fs::read(extra).await?);
+        }
+        for dependency in local_dependencies(&self.lang, &self.source) {
+            hasher.write(&tokio::fs::read(dependency).await?);
+        }
+        self.lang.extension().hash(&mut hasher);
+        match self.lang {
+            Lang::Cpp => self.cpp_command().hash(&mut hasher),
+            Lang::Rust => self.rustc_command().hash(&mut hasher),
+            _ => {}
+        }
+        self.extra_flags.hash(&mut hasher);
+        // The other compiler flags are fixed per release
+        env!("CARGO_PKG_VERSION").hash(&mut hasher);
+        Ok(format!("{:016x}", hasher.finish()))
+    }
+
+    /// Extra sources that are passed to the compiler. Only files in the same language are compiled,
+    /// the rest (like headers) are found by the compiler next to the source.
+    fn extra_compile_units(&self) -> impl Iterator<Item = &PathBuf> {
+        self.extra_sources.iter().filter(|extra| {
+            extra.extension().and_then(OsStr::to_str) == Some(self.lang.extension())
+        })
+    }
+
+    /// Records the result of a compiler invocation that wrote its binary to `output_path`
+    fn finish_compile(&mut self, output: &Output, output_path: PathBuf) {
+        info!("Finished compiling {}", self.name());
+        if output.status.success() {
+            let warnings = String::from_utf8_lossy(&output.stderr);
+            if self.show_warnings && !warnings.trim().is_empty() {
+                progress::suspend(|| {
+                    eprintln!(
+                        "{} {}\n{warnings}",
+                        self.name().bold(),
+                        "compiled with warnings:".yellow()
+                    );
+                });
+            }
+            self.compiled = Some(Ok(()));
+            self.binary = Some(output_path);
+        } else {
+            let mut err = format!("{}\n", self.name());
+            err.push_str(&String::from_utf8_lossy(&output.stderr));
+            self.compiled = Some(Err(err));
+        }
+    }
+
+    /// Builds the Cargo project in `project` and copies out the binary built from the source
+    async fn compile_cargo(&mut self, project: &Path) -> Result<()> {
+        info!(
+            "Compiling {} with cargo in {}",
+            self.name(),
+            project.display()
+        );
+        let output_path = self.binary_path();
+        let source = self.source.canonicalize()?;
+
+        let output = interrupt::output(
+            Command::new("cargo")
+                .arg("build")
+                .arg("--release")
+                .arg("--bins")
+                .arg("--manifest-path")
+                .arg(project.join("Cargo.toml"))
+                .arg(format!("--color={}", color_choice()))
+                .arg("--message-format=json-render-diagnostics"),
+        )
+        .await
+        .context("Couldn't compile Rust program. Make sure cargo is installed and in path.")?;
+        if !output.status.success() {
+            self.finish_compile(&output, output_path);
+            return Ok(());
+        }
+
+        // Cargo reports every binary it built, find the one whose main file is the source
+        let executable = String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter_map(|line| serde_json::from_str::<serde_json::Value>(line).ok())
+            .filter(|message| message["reason"] == "compiler-artifact")
+            .find(|message| {
+                message["target"]["src_path"].as_str().map(Path::new) == Some(source.as_path())
+            })
+            .and_then(|message| message["executable"].as_str().map(PathBuf::from))
+            .ok_or_else(|| {
+                anyhow!(
+                    "Cargo didn't build a binary from {}. Make sure it is the main file of a binary target in {}.",
+                    self.source.display(),
+                    project.join("Cargo.toml").display()
+                )
+            })?;
+        tokio::fs::copy(&executable, &output_path).await?;
+        self.finish_compile(&output, output_path);
+        Ok(())
+    }
+
+    #[allow(clippy::too_many_lines)]
     pub async fn compile(&mut self) -> Result<()> {
         if self.compiled.is_some() {
             bail!("Already compiled!");
         }
+        let _progress = self
+            .lang
+            .compiled()
+            .then(|| progress::start(format!("Compiling {}…", self.name())));
+        let cargo_project = if self.lang == Lang::Rust {
+            cargo_project(&self.source)
+        } else {
+            None
+        };
+        // Cargo keeps its own incremental build, and the binary depends on more than the source
+        if self.use_cache && self.lang.compiled() && cargo_project.is_none() {
+            self.cache_key = Some(self.compute_cache_key().await?);
+            let cached_path = self.binary_path();
+            if cached_path.is_file() {
+                info!(
+                    "Using cached binary for {} at {}",
+                    self.name(),
+                    cached_path.display()
+                );
+                self.binary = Some(cached_path);
+                self.compiled = Some(Ok(()));
+                return Ok(());
+            }
+        }
+        // A binary that is cut off by Ctrl-C would be picked up from the cache by the next run
+        let _unfinished = interrupt::remove_on_interrupt(&self.binary_path());
         match self.lang {
+            Lang::C => {
+                info!("Compiling {}", self.name());
+                let output_path = self.binary_path();
+
+                let output = interrupt::output(Command::new("gcc")
+                    .arg(self.source.as_os_str())
+                    .args(self.extra_compile_units())
+                    .arg("-o")
+                    .arg(&output_path)
+                    .arg(format!("-fdiagnostics-color={}", color_choice()))
+                    .arg("-g").arg("-O2").arg("-std=gnu11")
+                    .arg("-lm") // Link the math library after the source, like Kattis does
+                    .args(&self.extra_flags))
+                    .await
+                    .context("Couldn't compile C program. Make sure GNU gcc is installed and in path (this is the compiler that kattis uses).")?;
+
+                self.finish_compile(&output, output_path);
+            }
             Lang::Cpp => {
                 info!("Compiling {}", self.name());
-                let mut output_path = std::env::temp_dir();
-                output_path.push("kattis/");
-                output_path.push(format!(
-                    "cpp-{}",
-                    self.source.file_stem().unwrap().to_str().unwrap()
-                ));
-
-                let output = Command::new("g++")
+                let output_path = self.binary_path();
+
+                let compiler = self.cpp_command();
+                let output = interrupt::output(Command::new(compiler)
                     .arg(self.source.as_os_str())
+                    .args(self.extra_compile_units())
                     .arg("-o")
                     .arg(&output_path)
-                    .arg("-fdiagnostics-color=always") // Colored output
+                    .arg(format!("-fdiagnostics-color={}", color_choice()))
                     .arg("-g").arg("-O2").arg("-std=gnu++17") // Kattis standards as of Sep 2020
-                    .output()
+                    .args(&self.extra_flags))
                     .await
-                    .expect("Couldn't compile C++ program. Make sure GNU g++ is installed and in path (this is the compiler that kattis uses).");
+                    .with_context(|| format!("Couldn't compile C++ program with {compiler}. Make sure it is installed and in path."))?;
 
-                info!("Finished compiling {}", self.name());
-                if output.status.success() {
-                    self.compiled = Some(Ok(()));
-                    self.binary = Some(output_path.clone());
-                } else {
-                    let mut err = format!("{}\n", self.name());
-                    err.push_str(&String::from_utf8(output.stderr).unwrap());
-                    self.compiled = Some(Err(err));
-                    //bail!("Compile Error!")
-                }
+                self.finish_compile(&output, output_path);
+            }
+            Lang::Rust if cargo_project.is_some() => {
+                self.compile_cargo(&cargo_project.unwrap()).await?;
             }
             Lang::Rust => {
-                let mut output_path = std::env::temp_dir();
-                output_path.push("kattis/");
-                output_path.push(format!(
-                    "rs-{}",
-                    self.source.file_stem().unwrap().to_str().unwrap()
-                ));
-
-                let output = Command::new("rustc")
+                info!("Compiling {}", self.name());
+                let output_path = self.binary_path();
+
+                let compiler = self.rustc_command();
+                let output = interrupt::output(Command::new(compiler)
                     .arg(self.source.as_os_str())
                     .arg("-o")
                     .arg(&output_path)
-                    .arg("--color=always")
-                    .output()
+                    .arg(format!("--color={}", color_choice()))
+                    .arg("-O").arg("--edition=2021") // Kattis compiles Rust optimized
+                    .args(&self.extra_flags))
                     .await
-                    .expect(
-                        "Couldn't compile Rust program. Make sure rustc is installed and in path.",
-                    );
+                    .with_context(|| format!("Couldn't compile Rust program with {compiler}. Make sure it is installed and in path."))?;
 
-                if output.status.success() {
-                    self.compiled = Some(Ok(()));
-                    self.binary = Some(output_path.clone());
-                } else {
-                    let mut err =
-                        format!("{}\n", self.source.file_name().unwrap().to_str().unwrap());
-                    err.push_str(&String::from_utf8_lossy(&output.stderr));
-                    self.compiled = Some(Err(err));
-                    // bail!("Rust Compile Error!");
+                self.finish_compile(&output, output_path);
+            }
+            Lang::Kotlin => {
+                info!("Compiling {}", self.name());
+                let output_path = self.binary_path();
+
+                let output = interrupt::output(
+                    Command::new("kotlinc")
+                        .arg(self.source.as_os_str())
+                        .args(self.extra_compile_units())
+                        .arg("-include-runtime")
+                        .arg("-d")
+                        .arg(&output_path)
+                        .args(&self.extra_flags),
+                )
+                .await
+                .context(
+                    "Couldn't compile Kotlin program. Make sure kotlinc is installed and in path.",
+                )?;
+
+                self.finish_compile(&output, output_path);
+            }
+            Lang::CSharp => {
+                info!("Compiling {}", self.name());
+                let output_path = self.binary_path();
+                let compiler = ["mcs", "csc"]
+                    .into_iter()
+                    .find(|compiler| in_path(compiler))
+                    .ok_or_else(|| anyhow!("Couldn't find mcs or csc in path. Make sure Mono or the .NET compiler is installed."))?;
+                if let Some(runtime) = self.lang.interpreter().filter(|runtime| !in_path(runtime)) {
+                    bail!("Couldn't find {runtime} in path, which is needed to run C# programs. Make sure Mono is installed.");
+                }
+
+                let output = interrupt::output(
+                    Command::new(compiler)
+                        .arg("-optimize+")
+                        .arg(format!("-out:{}", output_path.display()))
+                        .args(&self.extra_flags)
+                        .arg(self.source.as_os_str())
+                        .args(self.extra_compile_units()),
+                )
+                .await
+                .with_context(|| format!("Couldn't compile C# program with {compiler}."))?;
+
+                self.finish_compile(&output, output_path);
+            }
+            Lang::Haskell => {
+                info!("Compiling {}", self.name());
+                let output_path = self.binary_path();
+                // ghc leaves .hi and .o files next to the source unless told otherwise
+                let mut build_dir = output_path.clone();
+                build_dir.as_mut_os_string().push("-build");
+
+                let output = interrupt::output(
+                    Command::new("ghc")
+                        .arg("-O2")
+                        .arg("-o")
+                        .arg(&output_path)
+                        .arg("-outputdir")
+                        .arg(&build_dir)
+                        .args(&self.extra_flags)
+                        .arg(self.source.as_os_str()),
+                )
+                .await;
+                // Only the linked binary is needed, so the object files don't pile up
+                if let Err(e) = tokio::fs::remove_dir_all(&build_dir).await {
+                    if e.kind() != std::io::ErrorKind::NotFound {
+                        warn!(
+                            "Failed to remove the ghc build directory {}: {e}",
+                            build_dir.display()
+                        );
+                    }
+                }
+                let output = output.context(
+                    "Couldn't compile Haskell program. Make sure ghc is installed and in path.",
+                )?;
+
+                self.finish_compile(&output, output_path);
+            }
+            Lang::Go => {
+                info!("Compiling {}", self.name());
+                let output_path = self.binary_path();
+
+                let go_build = |module_aware: bool| {
+                    let mut command = Command::new("go");
+                    command
+                        .arg("build")
+                        .arg("-o")
+                        .arg(&output_path)
+                        .args(&self.extra_flags) // go build only takes flags before the files
+                        .arg(self.source.as_os_str())
+                        .args(self.extra_compile_units());
+                    if !module_aware {
+                        command.env("GO111MODULE", "off");
+                    }
+                    command
+                };
+
+                let mut output = interrupt::output(&mut go_build(true)).await.context(
+                    "Couldn't compile Go program. Make sure go is installed and in path.",
+                )?;
+                if !output.status.success()
+                    && String::from_utf8_lossy(&output.stderr).contains("go.mod")
+                {
+                    info!("go build needs a module context, retrying with GO111MODULE=off");
+                    output = interrupt::output(&mut go_build(false)).await?;
                 }
+
+                self.finish_compile(&output, output_path);
             }
-            Lang::Python | Lang::Bash => {
+            Lang::Python => {
+                self.interpreter = Some(match self.python_impl {
+                    PythonImpl::CPython => python_interpreter().await?,
+                    PythonImpl::PyPy if in_path("pypy3") => "pypy3".to_string(),
+                    PythonImpl::PyPy => bail!(
+                        "Couldn't find pypy3 in path, which is needed for --python-impl pypy. \
+                        Make sure PyPy 3 is installed."
+                    ),
+                });
+                self.binary = Some(self.source.clone());
+                self.compiled = Some(Ok(()));
+            }
+            Lang::JavaScript | Lang::Ruby | Lang::Bash => {
+                self.interpreter = self.lang.interpreter().map(String::from);
                 self.binary = Some(self.source.clone());
                 self.compiled = Some(Ok(()));
             }
@@ -211,83 +760,332 @@ impl Program {
         Ok(())
     }
 
-    fn spawn_process(&self, stdin_file: std::fs::File) -> Result<Child> {
-        if let Some(bin) = &self.binary {
-            match self.lang {
-                Lang::Cpp | Lang::Rust => Ok(Command::new(bin)
-                    .stdin(Stdio::from(stdin_file))
-                    .stdout(Stdio::piped())
-                    .stderr(Stdio::piped())
-                    .spawn()?),
-                Lang::Python => Ok(Command::new("python")
-                    .arg(bin)
-                    .stdin(Stdio::from(stdin_file))
-                    .stderr(Stdio::piped())
-                    .stdout(Stdio::piped())
-                    .spawn()?),
-                Lang::Bash => Ok(Command::new("bash")
-                    .arg(bin)
-                    .stdin(Stdio::from(stdin_file))
-                    .stderr(Stdio::piped())
-                    .stdout(Stdio::piped())
-                    .spawn()?),
-            }
-        } else {
+    fn spawn_process(&self, stdin: Stdio, stdout: Stdio, mem_limit: Option<u64>) -> Result<Child> {
+        let Some(bin) = &self.binary else {
             bail!("Program not compiled");
+        };
+        let mut command = match self.lang {
+            Lang::C | Lang::Cpp | Lang::Rust | Lang::Haskell | Lang::Go => Command::new(bin),
+            Lang::Kotlin => {
+                let mut command = Command::new("java");
+                if let Some(megabytes) = mem_limit {
+                    command.arg(format!("-Xmx{megabytes}m"));
+                }
+                command.arg("-jar").arg(bin);
+                command
+            }
+            Lang::CSharp => self.lang.interpreter().map_or_else(
+                || Command::new(bin),
+                |runtime| {
+                    let mut command = Command::new(runtime);
+                    if let Some(megabytes) = mem_limit {
+                        command.env("MONO_GC_PARAMS", format!("max-heap-size={megabytes}m"));
+                    }
+                    command.arg(bin);
+                    command
+                },
+            ),
+            Lang::Python | Lang::JavaScript | Lang::Ruby | Lang::Bash => {
+                let Some(interpreter) = &self.interpreter else {
+                    bail!("No interpreter chosen for {}", self.name());
+                };
+                let mut command = Command::new(interpreter);
+                command.arg(bin);
+                command
+            }
+        };
+        // The JVM and Mono reserve far more address space than they use and fail to start under
+        // RLIMIT_AS, so their heap is capped above instead
+        #[cfg(unix)]
+        if let Some(megabytes) =
+            mem_limit.filter(|_| !matches!(self.lang, Lang::Kotlin | Lang::CSharp))
+        {
+            let bytes: libc::rlim_t = megabytes.saturating_mul(1024 * 1024);
+            // SAFETY: setrlimit is async-signal-safe and only affects the forked child
+            unsafe {
+                command.pre_exec(move || {
+                    let limit = libc::rlimit {
+                        rlim_cur: bytes,
+                        rlim_max: bytes,
+                    };
+                    if libc::setrlimit(libc::RLIMIT_AS, &raw const limit) == 0 {
+                        Ok(())
+                    } else {
+                        Err(std::io::Error::last_os_error())
+                    }
+                });
+            }
         }
+        #[cfg(not(unix))]
+        let _ = mem_limit;
+
+        command
+            .stdin(stdin)
+            .stdout(stdout)
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| {
+                let interpreter = self
+                    .interpreter
+                    .as_deref()
+                    .or_else(|| self.lang.interpreter());
+                match (e.kind(), interpreter) {
+                    (std::io::ErrorKind::NotFound, Some(interpreter)) => anyhow!(
+                        "Couldn't run {} program. Make sure {interpreter} is installed and in path.",
+                        self.lang
+                    ),
+                    _ => e.into(),
+                }
+            })
     }
 
-    async fn run_problem<'a>(&'a self, pio: &'a ProblemIO) -> Result<(&'a ProblemIO, Output)> {
+    /// Runs the program on a single input, measuring the wall-clock time of each run. With
+    /// `--repeat` the program is run again for timing only, as long as the first run succeeded.
+    async fn run_problem<'a>(
+        &'a self,
+        pio: &'a ProblemIO,
+        options: &CheckOptions,
+    ) -> Result<(&'a ProblemIO, RunResult, Vec<Duration>)> {
+        if let Some(judge) = &options.interactive {
+            return self.run_interactive(pio, judge, options).await;
+        }
         info!("Running problem {}", self.name());
-        match self.spawn_process(std::fs::File::open(&pio.input)?) {
-            Ok(child) => {
-                let results = child.wait_with_output().await?;
-                info!("Finished running problem {}", self.name());
-                Ok((pio, results))
+        let (output, runtime) = self.run_once(pio, options, options.stream).await?;
+        let run_result = match output {
+            Some(output) => check_problem_output(pio, &output, options).await?,
+            None => RunResult::TimedOut,
+        };
+        let mut runtimes = vec![runtime];
+        if matches!(run_result, RunResult::Completed(_) | RunResult::Checked(..)) {
+            for _ in 1..options.repeat {
+                let (output, runtime) = self.run_once(pio, options, false).await?;
+                runtimes.push(runtime);
+                if output.is_none() {
+                    break;
+                }
             }
-            Err(e) => Err(e),
+        }
+        info!("Finished running problem {}", self.name());
+        Ok((pio, run_result, runtimes))
+    }
+
+    /// Runs the program once on the input. The output is `None` if the run was killed for
+    /// exceeding the timeout. With `stream`, the output is also printed to stderr as it is written.
+    async fn run_once(
+        &self,
+        pio: &ProblemIO,
+        options: &CheckOptions,
+        stream: bool,
+    ) -> Result<(Option<Output>, Duration)> {
+        let start = Instant::now();
+        let stdin = Stdio::from(std::fs::File::open(&pio.input)?);
+        let mut child = self.spawn_process(stdin, Stdio::piped(), options.mem_limit)?;
+        let _running = interrupt::kill_on_interrupt(&child);
+        let run = async {
+            if !stream {
+                return child.wait_with_output().await;
+            }
+            progress::suspend(|| eprintln!("{}", format!("Output of case {}:", pio.name).bold()));
+            let mut stdout = child.stdout.take().unwrap();
+            let tee = async {
+                let mut captured = Vec::new();
+                let mut buffer = [0; 8192];
+                loop {
+                    let read = stdout.read(&mut buffer).await?;
+                    if read == 0 {
+                        return Ok::<_, std::io::Error>(captured);
+                    }
+                    progress::suspend(|| {
+                        let mut stderr = std::io::stderr().lock();
+                        stderr
+                            .write_all(&buffer[..read])
+                            .and_then(|()| stderr.flush())
+                    })?;
+                    captured.extend_from_slice(&buffer[..read]);
+                }
+            };
+            let (captured, output) = join(tee, child.wait_with_output()).await;
+            let mut output = output?;
+            output.stdout = captured?;
+            Ok(output)
+        };
+        // The child is killed on drop, which happens if the timeout is hit
+        let output = tokio::time::timeout(options.timeout, run).await;
+        let runtime = start.elapsed();
+        Ok((output.ok().transpose()?, runtime))
+    }
+
+    /// Runs the program against an interactive judge, which reads the program's output and writes
+    /// its input. The judge is run as `judge <input> <answer>` and accepts by exiting with status
+    /// 0 or 42, like the Kattis output validators.
+    async fn run_interactive<'a>(
+        &'a self,
+        pio: &'a ProblemIO,
+        judge: &Path,
+        options: &CheckOptions,
+    ) -> Result<(&'a ProblemIO, RunResult, Vec<Duration>)> {
+        info!("Running problem {} interactively", self.name());
+        let start = Instant::now();
+        let mut judge_process = Command::new(judge)
+            .arg(pio.input.as_os_str())
+            .arg(pio.output.as_os_str())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .with_context(|| format!("Failed to run judge {}", judge.display()))?;
+        let _judge_running = interrupt::kill_on_interrupt(&judge_process);
+        // Connect the program and the judge to each other
+        let to_program: Stdio = judge_process.stdout.take().unwrap().try_into()?;
+        let from_program: Stdio = judge_process.stdin.take().unwrap().try_into()?;
+        let program = self.spawn_process(to_program, from_program, options.mem_limit)?;
+        let _running = interrupt::kill_on_interrupt(&program);
+
+        let outputs = tokio::time::timeout(
+            options.timeout,
+            join(program.wait_with_output(), judge_process.wait_with_output()),
+        )
+        .await;
+        let runtime = start.elapsed();
+        info!("Finished running problem {}", self.name());
+        let run_result = match outputs {
+            Ok((program_output, judge_output)) => {
+                let (program_output, judge_output) = (program_output?, judge_output?);
+                if program_output.status.success() && crash_reason(program_output.status).is_none()
+                {
+                    let accepted =
+                        judge_output.status.success() || judge_output.status.code() == Some(42);
+                    RunResult::Checked(
+                        accepted,
+                        String::from_utf8_lossy(&judge_output.stderr).to_string(),
+                    )
+                } else {
+                    runtime_error(&program_output, options.mem_limit.is_some())
+                }
+            }
+            Err(_) => RunResult::TimedOut,
+        };
+        Ok((pio, run_result, vec![runtime]))
+    }
+
+    /// Runs the program on `input` without checking the output, as done by `stress`. Returns the
+    /// output, or what went wrong if the program didn't exit normally.
+    pub async fn output_for(
+        &self,
+        input: &Path,
+        timeout: Duration,
+    ) -> Result<std::result::Result<String, RunResult>> {
+        let stdin = Stdio::from(std::fs::File::open(input)?);
+        let child = self.spawn_process(stdin, Stdio::piped(), None)?;
+        let _running = interrupt::kill_on_interrupt(&child);
+        let Ok(output) = tokio::time::timeout(timeout, child.wait_with_output()).await else {
+            return Ok(Err(RunResult::TimedOut));
+        };
+        let output = output?;
+        if output.status.success() && crash_reason(output.status).is_none() {
+            Ok(Ok(String::from_utf8_lossy(&output.stdout).to_string()))
+        } else {
+            Ok(Err(runtime_error(&output, false)))
+        }
+    }
+
+    /// The compiler output, if compiling failed
+    pub fn compile_error(&self) -> Option<&str> {
+        match &self.compiled {
+            Some(Err(compile_error)) => Some(compile_error),
+            _ => None,
         }
     }
 
     pub fn run_problems<'a>(
         &'a self,
         ios: &'a [ProblemIO],
-    ) -> impl Stream<Item = Result<(&ProblemIO, Output)>> + 'a {
-        let mut tasks = FuturesOrdered::new();
-        for (_i, pio) in ios.iter().enumerate() {
-            let task = self.run_problem(pio);
-            tasks.push_back(task);
-        }
-        tasks
+        options: &'a CheckOptions,
+    ) -> impl Stream<Item = Result<(&'a ProblemIO, RunResult, Vec<Duration>)>> + 'a {
+        iter(ios)
+            .map(move |pio| self.run_problem(pio, options))
+            .buffered(options.jobs)
     }
 
-    pub async fn to_string(&self) -> Result<String> {
-        // Read from source
-        let mut output = String::new();
-        tokio::fs::File::open(&self.source)
-            .await?
-            .read_to_string(&mut output)
-            .await?;
+    /// Warnings for lines of the source that read their input from a file instead of stdin
+    pub async fn file_input_lints(&self) -> Result<Vec<String>> {
+        let pattern = Regex::new(self.lang.file_input_pattern())?;
+        let source = self.read_source().await?;
+        Ok(source
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| pattern.is_match(line))
+            .map(|(i, line)| {
+                format!(
+                    "{}:{} reads from a file, but Kattis only gives the input on stdin:\n    {}",
+                    self.name(),
+                    i + 1,
+                    line.trim()
+                )
+            })
+            .collect())
+    }
 
-        Ok(output)
+    /// The contents of the source file
+    pub async fn read_source(&self) -> Result<String> {
+        tokio::fs::read_to_string(&self.source)
+            .await
+            .with_context(|| format!("Failed to read {}", self.source.display()))
+    }
+
+    /// The class Kattis starts the program from. kotlinc puts the top level functions of
+    /// `name.kt` in the class `NameKt`.
+    fn mainclass(&self, problem_name: &str) -> String {
+        if self.lang != Lang::Kotlin {
+            return problem_name.to_string();
+        }
+        let stem = self
+            .source
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy();
+        let mut chars = stem.chars();
+        let first = chars
+            .next()
+            .map(|c| c.to_uppercase().to_string())
+            .unwrap_or_default();
+        let rest: String = chars
+            .map(|c| if c.is_alphanumeric() { c } else { '_' })
+            .collect();
+        format!("{first}{rest}Kt")
     }
 
     pub async fn submit(
         &self,
         problem_name: &str,
         submission_viewer: SubmissionViewerType,
+        poll: PollOptions,
+        dry_run: bool,
     ) -> Result<()> {
+        let mut files = vec![(
+            self.name().to_string(),
+            self.read_source().await?.into_bytes(),
+        )];
+        for extra in &self.extra_sources {
+            let name = extra
+                .file_name()
+                .and_then(OsStr::to_str)
+                .ok_or_else(|| anyhow!("Invalid file name {}", extra.display()))?;
+            let contents = tokio::fs::read(extra)
+                .await
+                .with_context(|| format!("Failed to read {}", extra.display()))?;
+            files.push((name.to_string(), contents));
+        }
         submit(
             format!("{}", &self.lang),
             problem_name.to_string(),
-            self.source
-                .file_name()
-                .unwrap()
-                .to_str()
-                .unwrap()
-                .to_string(),
-            self.to_string().await.unwrap(),
+            self.mainclass(problem_name),
+            files,
             submission_viewer,
+            poll,
+            dry_run,
         )
         .await
     }
@@ -295,41 +1093,130 @@ impl Program {
 
 #[derive(Sequence, PartialEq, Clone, Eq, Debug)]
 pub enum Lang {
+    C,
     Cpp,
     Rust,
+    Kotlin,
+    CSharp,
+    Haskell,
+    Go,
     Python,
+    JavaScript,
+    Ruby,
     Bash,
 }
 
 impl Lang {
     pub const fn compiled(&self) -> bool {
         match self {
-            Self::Cpp | Self::Rust => true,
-            Self::Python | Self::Bash => false,
+            Self::C
+            | Self::Cpp
+            | Self::Rust
+            | Self::Kotlin
+            | Self::CSharp
+            | Self::Haskell
+            | Self::Go => true,
+            Self::Python | Self::JavaScript | Self::Ruby | Self::Bash => false,
         }
     }
     pub const fn extension(&self) -> &'static str {
         match self {
+            Self::C => "c",
             Self::Cpp => "cpp",
             Self::Rust => "rs",
+            Self::Kotlin => "kt",
+            Self::CSharp => "cs",
+            Self::Haskell => "hs",
+            Self::Go => "go",
             Self::Python => "py",
+            Self::JavaScript => "js",
+            Self::Ruby => "rb",
             Self::Bash => "sh",
         }
     }
 
+    /// Program that runs the source or compiled artifact, if it isn't run directly
+    pub const fn interpreter(&self) -> Option<&'static str> {
+        match self {
+            Self::C | Self::Cpp | Self::Rust | Self::Haskell | Self::Go => None,
+            Self::Kotlin => Some("java"),
+            // .NET executables run directly on Windows
+            Self::CSharp if cfg!(windows) => None,
+            Self::CSharp => Some("mono"),
+            Self::Python => Some("python3"),
+            Self::JavaScript => Some("node"),
+            Self::Ruby => Some("ruby"),
+            Self::Bash => Some("bash"),
+        }
+    }
+
+    /// How the program is run on the sample inputs, for listing the supported languages
+    pub const fn run_command(&self) -> &'static str {
+        match self {
+            Self::C | Self::Cpp | Self::Rust | Self::Haskell | Self::Go => "<binary>",
+            Self::Kotlin => "java -jar <binary>",
+            Self::CSharp if cfg!(windows) => "<binary>",
+            Self::CSharp => "mono <binary>",
+            Self::Python => "python3 <source> (pypy3 with --python-impl pypy)",
+            Self::JavaScript => "node <source>",
+            Self::Ruby => "ruby <source>",
+            Self::Bash => "bash <source>",
+        }
+    }
+
+    /// Pattern for code that reads a named file, which fails on Kattis where the input is only
+    /// given on stdin
+    const fn file_input_pattern(&self) -> &'static str {
+        match self {
+            Self::C => r#"\bfreopen\s*\(|\bfopen\s*\(\s*""#,
+            Self::Cpp => r#"\bfreopen\s*\(|\bfopen\s*\(\s*"|\bifstream\b[^;]*""#,
+            Self::Rust => r"\bFile::open\s*\(|\bfs::read(_to_string)?\s*\(",
+            Self::Kotlin => r#"\bFile\s*\(\s*"|\bFileReader\s*\("#,
+            Self::CSharp => {
+                r#"\bFile\.(ReadAll\w*|OpenText|OpenRead)\s*\(|\bStreamReader\s*\(\s*""#
+            }
+            Self::Haskell => r#"\b(readFile|openFile)\s+""#,
+            Self::Go => r"\bos\.Open(File)?\s*\(",
+            Self::Python => r#"\bopen\s*\(\s*[rbfu]*["']"#,
+            Self::JavaScript => r#"\breadFileSync\s*\(\s*["'`]\w"#,
+            Self::Ruby => r#"\bFile\.(open|read|readlines|foreach)\s*\(?\s*["']"#,
+            Self::Bash => r"<\s*[\w.-]+\.(in|txt)\b",
+        }
+    }
+
+    /// Name of the language's table in the config file
+    pub const fn config_name(&self) -> &'static str {
+        match self {
+            Self::C => "c",
+            Self::Cpp => "cpp",
+            Self::Rust => "rust",
+            Self::Kotlin => "kotlin",
+            Self::CSharp => "csharp",
+            Self::Haskell => "haskell",
+            Self::Go => "go",
+            Self::Python => "python",
+            Self::JavaScript => "javascript",
+            Self::Ruby => "ruby",
+            Self::Bash => "bash",
+        }
+    }
+
     pub fn from_extension(ext: impl AsRef<str>) -> Option<Self> {
         match ext.as_ref() {
+            "c" => Some(Self::C),
             "cpp" => Some(Self::Cpp),
             "py" => Some(Self::Python),
             "rs" => Some(Self::Rust),
+            "kt" => Some(Self::Kotlin),
+            "cs" => Some(Self::CSharp),
+            "hs" => Some(Self::Haskell),
+            "go" => Some(Self::Go),
+            "js" => Some(Self::JavaScript),
+            "rb" => Some(Self::Ruby),
             "sh" => Some(Self::Bash),
             _ => None,
         }
     }
-
-    pub fn is_valid_extension(ext: &str) -> bool {
-        Self::from_extension(ext).is_some()
-    }
 }
 
 /// Used by submission system
@@ -339,9 +1226,16 @@ impl fmt::Display for Lang {
             f,
             "{}",
             match self {
+                Self::C => "C",
                 Self::Cpp => "C++",
                 Self::Python => "Python 3",
+                Self::JavaScript => "JavaScript (Node.js)",
                 Self::Rust => "Rust",
+                Self::Kotlin => "Kotlin",
+                Self::CSharp => "C#",
+                Self::Haskell => "Haskell",
+                Self::Go => "Go",
+                Self::Ruby => "Ruby",
                 Self::Bash => "Bash",
             }
         )
@@ -368,32 +1262,97 @@ impl fmt::Display for Lang {
 //         .collect()
 // }
 
-pub fn find_source_from_path(path: &Path) -> Result<ProblemSource> {
+/// Finds `<path>.<ext>` for a source given without its extension, such as a bare problem name
+fn source_without_extension(path: &Path, lang: Option<&Lang>) -> Result<PathBuf> {
+    let sources = all::<Lang>()
+        .filter(|candidate| lang.is_none_or(|lang| lang == candidate))
+        .map(|candidate| path.with_extension(candidate.extension()))
+        .filter(|source| source.is_file())
+        .collect_vec();
+    match sources.as_slice() {
+        [] => bail!("No source file found for {}", path.display()),
+        [source] => Ok(source.clone()),
+        _ => bail!(
+            "Found several sources for {}: {}. Choose one with --lang.",
+            path.display(),
+            sources.iter().map(|source| source.display()).join(", ")
+        ),
+    }
+}
+
+/// Finds the language and problem of a source file. The problem name is the file stem, unless
+/// `problem_override` is given. Only sources in `lang` are accepted if it is given. For a
+/// directory, the newest source in it is used like when no source is given.
+pub fn find_source_from_path(
+    path: &Path,
+    problem_override: Option<&str>,
+    lang_filter: Option<&Lang>,
+) -> Result<ProblemSource> {
+    if path.is_dir() {
+        return find_newest_source(path, problem_override, lang_filter)
+            .with_context(|| format!("Failed to find a source in {}", path.display()));
+    }
+    let source = source_from_path(path, problem_override, lang_filter)?;
+    if block_on(fetch::problem_exists(&source.problem_name))? {
+        Ok(source)
+    } else {
+        bail!(
+            "Could not find the problem {} at {}\n\
+            If the file isn't named after the problem, give its name with {} <NAME>.",
+            source.problem_name,
+            link::display_link(&link::problem_url(&source.problem_name)),
+            "--problem".bold()
+        );
+    }
+}
+
+/// Like `find_source_from_path`, without checking that the problem exists
+pub fn source_from_path(
+    path: &Path,
+    problem_override: Option<&str>,
+    lang_filter: Option<&Lang>,
+) -> Result<ProblemSource> {
+    let path = if path.extension().is_none() && !path.exists() {
+        &source_without_extension(path, lang_filter)?
+    } else {
+        path
+    };
     if !path.is_file() {
-        bail!("Path {path:?} is not a file");
+        bail!("Path {} is not a file", path.display());
     }
     let extension = path
         .extension()
-        .ok_or_else(|| anyhow!("Path {path:?} has no extension"))?;
+        .ok_or_else(|| anyhow!("Path {} has no extension", path.display()))?;
     let lang = Lang::from_extension(extension.to_string_lossy()).ok_or_else(|| {
         anyhow!(
-            "Extension {extension:?} from path {path:?} is not supported. Expected one of {}",
+            "Extension {} from path {} is not supported. Expected one of {}",
+            extension.to_string_lossy(),
+            path.display(),
             all::<Lang>().map(|l| l.extension()).join(", ")
         )
     })?;
-    let problem_name = path
-        .file_stem()
-        .ok_or_else(|| anyhow!("Problem name not found in path {path:?}"))?;
-
-    if block_on(fetch::problem_exists(&problem_name.to_string_lossy()))? {
-        Ok(ProblemSource {
-            problem_name: problem_name.to_string_lossy().to_string(),
-            path: path.to_path_buf(),
-            lang,
-        })
-    } else {
-        bail!("Could not find the problem {problem_name:?} at open.kattis.com/problem/{problem_name:?}");
+    if let Some(lang_filter) = lang_filter.filter(|lang_filter| **lang_filter != lang) {
+        bail!(
+            "{} is not a {} source, but --lang {} was given",
+            path.display(),
+            lang_filter.extension(),
+            lang_filter.extension()
+        );
     }
+    let problem_name = match problem_override {
+        Some(problem_name) => problem_name.to_string(),
+        None => path
+            .file_stem()
+            .ok_or_else(|| anyhow!("Problem name not found in path {}", path.display()))?
+            .to_string_lossy()
+            .to_string(),
+    };
+
+    Ok(ProblemSource {
+        problem_name,
+        path: path.to_path_buf(),
+        lang,
+    })
 }
 
 pub struct ProblemSource {
@@ -402,11 +1361,89 @@ pub struct ProblemSource {
     pub lang: Lang,
 }
 
-pub fn find_newest_source() -> Result<ProblemSource> {
-    let problem_path = walkdir::WalkDir::new(".")
+/// Local files that the compiler reads along with `source`: headers included with quotes in C
+/// and C++, and the files of `mod name;` declarations in Rust. Quoted includes that aren't found
+/// next to the including file come from the include path and are skipped.
+fn local_dependencies(lang: &Lang, source: &Path) -> Vec<PathBuf> {
+    static INCLUDE_RE: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r#"(?m)^\s*#\s*include\s*"([^"]+)""#).unwrap());
+    static MOD_RE: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r"(?m)^\s*(?:pub(?:\([^)]*\))?\s+)?mod\s+(\w+)\s*;").unwrap());
+
+    let Ok(source) = source.canonicalize() else {
+        return Vec::new();
+    };
+    let mut dependencies = Vec::new();
+    let mut pending = vec![source.clone()];
+    while let Some(file) = pending.pop() {
+        let Ok(text) = std::fs::read_to_string(&file) else {
+            continue;
+        };
+        let dir = file.parent().unwrap_or_else(|| Path::new(""));
+        let candidates: Vec<Vec<PathBuf>> = match lang {
+            Lang::C | Lang::Cpp => INCLUDE_RE
+                .captures_iter(&text)
+                .map(|include| vec![dir.join(&include[1])])
+                .collect(),
+            Lang::Rust => {
+                // The modules of `foo.rs` are in `foo/`, unless it is the crate root or a `mod.rs`
+                let dir = if file == source || file.ends_with("mod.rs") {
+                    dir.to_path_buf()
+                } else {
+                    dir.join(file.file_stem().unwrap_or_default())
+                };
+                MOD_RE
+                    .captures_iter(&text)
+                    .map(|module| {
+                        vec![
+                            dir.join(format!("{}.rs", &module[1])),
+                            dir.join(&module[1]).join("mod.rs"),
+                        ]
+                    })
+                    .collect()
+            }
+            _ => Vec::new(),
+        };
+        for path in candidates
+            .into_iter()
+            .filter_map(|paths| paths.iter().find_map(|path| path.canonicalize().ok()))
+        {
+            if path != source && !dependencies.contains(&path) {
+                dependencies.push(path.clone());
+                pending.push(path);
+            }
+        }
+    }
+    dependencies
+}
+
+/// The Cargo project the Rust source belongs to, if its `Cargo.toml` is next to the source or
+/// the source is under the project's `src` directory
+fn cargo_project(source: &Path) -> Option<PathBuf> {
+    let source = source.canonicalize().ok()?;
+    let project = source
+        .ancestors()
+        .skip(1)
+        .find(|dir| dir.join("Cargo.toml").is_file())?;
+    (source.parent() == Some(project) || source.starts_with(project.join("src")))
+        .then(|| project.to_path_buf())
+}
+
+/// Source files in `dir` and up to `--recurse` levels below it, skipping hidden files and
+/// whatever `.gitignore` excludes
+fn source_files(dir: &Path, lang: Option<&Lang>) -> Result<Vec<DirEntry>> {
+    let working_dir = std::env::current_dir()?;
+    let mut gitignore = Gitignore::default();
+    Ok(walkdir::WalkDir::new(dir)
         .follow_links(true)
         .max_depth(*RECURSE_DEPTH.get().unwrap())
         .into_iter()
+        .filter_entry(|de| {
+            let relative = de.path().strip_prefix(".").unwrap_or_else(|_| de.path());
+            de.depth() == 0
+                || !(de.file_name().to_string_lossy().starts_with('.')
+                    || gitignore.is_ignored(&working_dir.join(relative), de.file_type().is_dir()))
+        })
         .take(100_000) // Look through at most 100_000 files
         .filter_map(|f| -> Option<DirEntry> {
             // Filter out files that don't have the right extension
@@ -415,43 +1452,118 @@ pub fn find_newest_source() -> Result<ProblemSource> {
             let file_path = de.path();
             if !file_path.is_file() {
                 return None;
-            }; // Skip directories
-            let file_extension = file_path.extension()?.to_string_lossy();
-            if Lang::is_valid_extension(&file_extension) {
-                Some(de)
-            } else {
-                None
-            }
+            } // Skip directories
+            let file_lang = Lang::from_extension(file_path.extension()?.to_string_lossy())?;
+            lang.is_none_or(|lang| *lang == file_lang).then_some(de)
         })
-        .max_by_key(|de|  // Find the file modified the latest
-            de.metadata()
+        .collect())
+}
+
+/// Every source file under `dir` that is named after a problem, sorted by path
+pub fn find_all_sources(dir: &Path, lang: Option<&Lang>) -> Result<Vec<ProblemSource>> {
+    let mut not_problems = HashSet::new();
+    let mut sources = Vec::new();
+    for path in source_files(dir, lang)?
+        .into_iter()
+        .map(DirEntry::into_path)
+        .sorted()
+    {
+        let problem_name = path.file_stem().unwrap().to_string_lossy().to_string();
+        if not_problems.contains(&problem_name) {
+            continue;
+        }
+        if !block_on(fetch::problem_exists(&problem_name))? {
+            info!(
+                "Skipping {} since {problem_name} is not a problem on {}",
+                path.display(),
+                host()
+            );
+            not_problems.insert(problem_name);
+            continue;
+        }
+        let lang = Lang::from_extension(path.extension().unwrap().to_string_lossy()).unwrap();
+        sources.push(ProblemSource {
+            problem_name,
+            path,
+            lang,
+        });
+    }
+    if sources.is_empty() {
+        bail!(
+            "Found no source files named after a problem in {}",
+            dir.display()
+        );
+    }
+    Ok(sources)
+}
+
+/// The most recently modified source in `dir` and up to `--recurse` levels below it that is named
+/// after a problem, or the newest source at all if `problem_override` is given
+pub fn find_newest_source(
+    dir: &Path,
+    problem_override: Option<&str>,
+    lang: Option<&Lang>,
+) -> Result<ProblemSource> {
+    let mut candidates: Vec<(SystemTime, PathBuf)> = source_files(dir, lang)?
+        .into_iter()
+        .map(|de| {
+            let modified = de
+                .metadata()
                 .map_err(|e| anyhow!("Failed to get metadata from file with error: {e}"))
                 .and_then(|x| x.modified().map_err(Into::into))
-                .unwrap_or(SystemTime::UNIX_EPOCH))
-        .ok_or_else(|| anyhow!("No source files found."))?
-        .into_path(); // Get the path of the file
-
-    let file_stem = problem_path.file_stem()
-        .ok_or_else(|| anyhow!("No file stem found for file {problem_path:?}."))?;
-
-    let problem_name = file_stem.to_string_lossy();
-
-    if block_on(fetch::problem_exists(&problem_name))? {
-        let extension = problem_path
-            .extension()
-            .ok_or_else(|| anyhow!("Path {problem_path:?} has no extension"))?;
-        Ok(ProblemSource {
-            problem_name: problem_name.to_string(),
-            lang: Lang::from_extension(extension.to_string_lossy())
-                .ok_or_else(|| anyhow!("Unrecognized extension"))?,
-            path: problem_path,
+                .unwrap_or(SystemTime::UNIX_EPOCH);
+            (modified, de.into_path())
         })
-    } else {
-        bail!(
-            "Problem name {} does not exist on open.kattis.com",
-            problem_name.bold()
+        .collect();
+    if candidates.is_empty() {
+        match lang {
+            Some(lang) => bail!("No {} source files found.", lang.extension()),
+            None => bail!("No source files found."),
+        }
+    }
+    // Newest first, since that is most likely the problem being worked on
+    candidates.sort_by_key(|(modified, _)| std::cmp::Reverse(*modified));
+
+    if let Some(problem_name) = problem_override {
+        let (_, path) = candidates.swap_remove(0);
+        return find_source_from_path(&path, Some(problem_name), lang);
+    }
+
+    let mut not_problems = HashSet::new();
+    for (_, path) in candidates {
+        let Some(problem_name) = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+        else {
+            continue;
+        };
+        if not_problems.contains(&problem_name) {
+            continue;
+        }
+        if block_on(fetch::problem_exists(&problem_name))? {
+            let extension = path.extension().unwrap().to_string_lossy();
+            return Ok(ProblemSource {
+                problem_name,
+                lang: Lang::from_extension(extension).unwrap(),
+                path,
+            });
+        }
+        info!(
+            "Skipping {} since {problem_name} is not a problem on {}",
+            path.display(),
+            host()
         );
+        not_problems.insert(problem_name);
     }
+    bail!(
+        "None of the source files are named after a problem on {}: {}",
+        host(),
+        not_problems
+            .iter()
+            .sorted()
+            .map(|name| name.bold())
+            .join(", ")
+    );
 }
 
 struct ProblemInstance<'a> {
@@ -466,38 +1578,153 @@ enum ProblemInstanceResult {
     CompileError(String),
 }
 
-struct CaseRun {
+pub struct CaseRun {
     case_name: String,
     run_result: RunResult,
+    /// Runtimes of all the runs of the case, fastest first
+    runtimes: Vec<Duration>,
+}
+
+impl Serialize for CaseRun {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("CaseRun", 5)?;
+        state.serialize_field("name", &self.case_name)?;
+        state.serialize_field("passed", &self.passed())?;
+        state.serialize_field("runtime", &self.runtime().as_secs_f64())?;
+        match self.median_runtime() {
+            Some(median) => state.serialize_field("median_runtime", &median.as_secs_f64())?,
+            None => state.skip_field("median_runtime")?,
+        }
+        state.serialize_field("result", &self.run_result)?;
+        state.end()
+    }
 }
 
 impl CaseRun {
+    /// The case name and runtime followed by the input, if given, and the result
+    fn display(
+        &self,
+        program_name: &str,
+        input: Option<&str>,
+        time_limit: Option<Duration>,
+    ) -> String {
+        let result_print = self.run_result.display(program_name);
+        let input_print = input.map_or_else(String::new, |input| {
+            format!("{}\n{input}\n{}\n", "Input:".bold(), "Result:".bold())
+        });
+        // Runs close to the time limit locally are likely to be too slow on Kattis
+        let runtime = format!("{:.3}s", self.runtime().as_secs_f64());
+        let mut runtime = match time_limit {
+            Some(limit) if self.runtime() > limit => runtime.red(),
+            Some(limit) if self.runtime() > limit / 2 => runtime.yellow(),
+            _ => runtime.normal(),
+        }
+        .to_string();
+        if let Some(median) = self.median_runtime() {
+            write!(
+                runtime,
+                ", median {:.3}s of {} runs",
+                median.as_secs_f64(),
+                self.runtimes.len()
+            )
+            .unwrap();
+        }
+        // Even a correct output would get Time Limit Exceeded on Kattis
+        if time_limit.is_some_and(|limit| self.runtime() > limit) {
+            write!(runtime, ", {}", "over limit".red()).unwrap();
+        }
+        format!(
+            "{} ({runtime})\n{input_print}{result_print}",
+            &self.case_name.yellow().bold(),
+        )
+    }
+
+    /// The fastest run, which is the least affected by noise
+    fn runtime(&self) -> Duration {
+        self.runtimes[0]
+    }
+
+    fn median_runtime(&self) -> Option<Duration> {
+        (self.runtimes.len() > 1).then(|| self.runtimes[self.runtimes.len() / 2])
+    }
+
     pub const fn passed(&self) -> bool {
         match &self.run_result {
             RunResult::Completed(cr) => cr.failed.is_none(),
-            RunResult::RuntimeError(_, _) => false,
+            RunResult::RuntimeError(_, _)
+            | RunResult::TimedOut
+            | RunResult::MemoryLimitExceeded(_) => false,
+            RunResult::Checked(accepted, _) => *accepted,
         }
     }
 }
 
+#[derive(Serialize)]
+#[serde(tag = "status", content = "details", rename_all = "snake_case")]
 pub enum RunResult {
     Completed(ComparisonResult),
     RuntimeError(String, String), // Output from stderr, stdout
+    TimedOut,
+    MemoryLimitExceeded(String), // Output from stderr
+    Checked(bool, String),       // Accepted by the checker, output from the checker's stderr
 }
 
-lazy_static::lazy_static! {
-    static ref SEGFAULT_RE: Regex = Regex::new(r"signal: (\d+)").unwrap();
+impl RunResult {
+    /// What happened in the run, as printed below the case name
+    pub fn display(&self, program_name: &str) -> String {
+        match self {
+            Self::Completed(cr) => format!("{cr}\n"),
+            Self::RuntimeError(stderr, stdout) => {
+                let mut out = stderr.clone();
+                if !stdout.is_empty() {
+                    write!(
+                        out,
+                        "\nBefore crashing, {program_name} outputted:\n{stdout}"
+                    )
+                    .unwrap();
+                }
+                out
+            }
+            Self::TimedOut => format!("{}\n", "Time limit exceeded (local)".red()),
+            Self::MemoryLimitExceeded(stderr) => {
+                format!("{}\n{stderr}", "Memory limit exceeded (local)".red())
+            }
+            Self::Checked(accepted, feedback) => {
+                let verdict = if *accepted {
+                    "Success".green().bold()
+                } else {
+                    "Rejected by checker".red().bold()
+                };
+                format!("{verdict}\n{feedback}")
+            }
+        }
+    }
 }
 
-/// Compiles, fetches, runs and compares problem
+/// Compiles, fetches, runs and compares problem. Gives up before submitting if `cancel` is
+/// cancelled.
+#[allow(clippy::too_many_lines)]
 async fn check_problem(
     problem: &mut Problem,
-    force: bool,
-    submission_viewer: SubmissionViewerType,
-) -> Result<bool> {
+    options: &CheckOptions,
+    cancel: &CancellationToken,
+) -> Result<ProblemReport> {
+    if cancel.is_cancelled() {
+        return Ok(ProblemReport::cancelled(problem));
+    }
+    if options.submit_only {
+        submit_program(&problem.problem_name, &problem.submission, options).await?;
+        return Ok(ProblemReport {
+            problem: problem.problem_name.clone(),
+            program: problem.submission.name().to_string(),
+            outcome: Outcome::Submitted,
+            compile_error: None,
+            cases: Vec::new(),
+        });
+    }
     let should_submit = problem.submit;
     // Fetch problem IO
-    let future_io = fetch::problem(&problem.problem_name);
+    let future_io = problem_io(&problem.problem_name, options);
 
     // // Find source paths
     // if problem.submissions.is_empty() {
@@ -519,104 +1746,519 @@ async fn check_problem(
     // }
 
     // Compile programs and fetch the io for this problem
-    let (compile_result, io) = join(problem.submission.compile(), future_io).await;
+    let compile_and_fetch = join(problem.submission.compile(), future_io);
+    let Some((compile_result, io)) = cancel.run_until_cancelled(compile_and_fetch).await else {
+        return Ok(ProblemReport::cancelled(problem));
+    };
     compile_result?;
 
     // let compiled_programs = compiled_programs.into_iter().collect::<Vec<_>>();
 
     let io = io?;
 
-    let problem_instance = run_problem(problem, &io).await;
+    let (io, available_cases) = select_cases(io, &options.cases)?;
+
+    if options.lint {
+        for lint in problem.submission.file_input_lints().await? {
+            progress::suspend(|| eprintln!("{} {lint}", "Warning:".yellow().bold()));
+        }
+    }
+
+    let Some(problem_instance) = cancel
+        .run_until_cancelled(run_problem(problem, &io, options))
+        .await
+    else {
+        return Ok(ProblemReport::cancelled(problem));
+    };
+    let problem_instance = problem_instance?;
 
     info!("Printing results");
-    println!("{}", &problem.problem_name.bold());
+    let human_output = options.output == OutputFormat::Human;
     let program_name = problem_instance.program.name();
     match problem_instance.result {
         ProblemInstanceResult::Ran(cases) => {
-            let mut failed_any = false;
-            let mut case_print = String::new();
-            for case in cases {
-                if !case.passed() {
-                    failed_any = true;
-                }
-                let result_print = match case.run_result {
-                    RunResult::Completed(cr) => format!("{cr}\n"),
-                    RunResult::RuntimeError(stderr, stdout) => {
-                        let mut out = stderr.clone();
-                        if !stdout.is_empty() {
-                            out.push_str(&format!(
-                                "\nBefore crashing, {program_name} outputted:\n{stdout}"
-                            ));
+            let time_limit = fetch::time_limit(&problem.problem_name);
+            let (failed_any, case_print) =
+                format_cases(&cases, &io, program_name, time_limit, options);
+            if human_output && (failed_any || !options.quiet) {
+                progress::suspend(|| {
+                    if options.compact {
+                        println!(
+                            "{}",
+                            compact_line(&problem.problem_name, &cases, failed_any)
+                        );
+                        if !failed_any {
+                            return;
                         }
-                        out
                     }
-                };
-                case_print.push_str(&format!("{}\n", &case.case_name.yellow().bold()));
-                case_print.push_str(&result_print);
+                    let limit = time_limit
+                        .map(|limit| format!(" (limit: {:.1}s)", limit.as_secs_f64()))
+                        .unwrap_or_default();
+                    println!("{}{limit}", problem_heading(&problem.problem_name));
+                    if let Some(available_cases) = &available_cases {
+                        println!("{}", format!("Available cases: {available_cases}").dimmed());
+                    }
+                    println!("{program_name}\n{case_print}");
+                });
             }
-            println!("{program_name}\n{case_print}");
 
-            if should_submit && (!failed_any || force) {
-                if let Err(e) = problem_instance
-                    .program
-                    .submit(&problem.problem_name, submission_viewer)
-                    .await
+            let outcome = Outcome::of_cases(&cases, failed_any);
+            if should_submit && (!failed_any || options.force) {
+                if failed_any {
+                    let warning = forced_submit_warning(&cases);
+                    progress::suspend(|| eprintln!("{} {warning}", "Warning:".yellow().bold()));
+                }
+                if let Err(e) =
+                    submit_program(&problem.problem_name, problem_instance.program, options).await
                 {
                     eprintln!("{}{e}", "Error:\n".bold().red());
                 }
-            } else {
-                return Ok(!failed_any)
             }
+            Ok(ProblemReport {
+                problem: problem.problem_name.clone(),
+                program: program_name.to_string(),
+                outcome,
+                compile_error: None,
+                cases,
+            })
         }
         ProblemInstanceResult::CompileError(compile_error) => {
-            eprintln!("{compile_error}");
-            return Ok(false);
+            if human_output {
+                progress::suspend(|| {
+                    if options.compact {
+                        let status = "COMPILE ERROR".red().bold();
+                        println!("{}  {status}", compact_name(&problem.problem_name));
+                    } else {
+                        println!("{}", problem_heading(&problem.problem_name));
+                    }
+                    eprintln!("{compile_error}");
+                });
+            }
+            Ok(ProblemReport {
+                problem: problem.problem_name.clone(),
+                program: program_name.to_string(),
+                outcome: Outcome::CompileError,
+                compile_error: Some(compile_error),
+                cases: Vec::new(),
+            })
+        }
+    }
+}
+
+/// The samples, or the case given with --input, along with the test cases from --test-dir. The
+/// samples are optional when --test-dir is given.
+async fn problem_io(problem_name: &str, options: &CheckOptions) -> Result<Vec<ProblemIO>> {
+    let io = match &options.single_case {
+        Some((input, answer)) => fetch::single_case(input, answer).map(|pio| vec![pio]),
+        None => fetch::problem(problem_name, options.sample_max_age).await,
+    };
+    let Some(test_dir) = &options.test_dir else {
+        return io;
+    };
+    let mut local_io = fetch::local_problem(test_dir)?;
+    match io {
+        Ok(mut io) => {
+            io.append(&mut local_io);
+            Ok(io)
+        }
+        Err(e) => {
+            warn!(
+                "Failed to fetch samples for {problem_name}: {e}\nOnly running the test cases in {}",
+                test_dir.display()
+            );
+            Ok(local_io)
+        }
+    }
+}
+
+/// Keeps the test cases named in `names`. When filtering, the names of all cases are returned as
+/// well so they can be shown.
+fn select_cases(io: Vec<ProblemIO>, names: &[String]) -> Result<(Vec<ProblemIO>, Option<String>)> {
+    if names.is_empty() {
+        return Ok((io, None));
+    }
+    let available = io.iter().map(|pio| pio.name.as_str()).join(", ");
+    if let Some(unknown) = names
+        .iter()
+        .find(|name| !io.iter().any(|pio| &&pio.name == name))
+    {
+        bail!("There is no test case named {unknown}. Available cases: {available}");
+    }
+    let selected = io
+        .into_iter()
+        .filter(|pio| names.contains(&pio.name))
+        .collect();
+    Ok((selected, Some(available)))
+}
+
+/// The problem heading padded so that the columns of compact lines line up
+fn compact_name(problem_name: &str) -> String {
+    const WIDTH: usize = 20;
+    let padding = " ".repeat(WIDTH.saturating_sub(problem_name.chars().count()));
+    format!("{}{padding}", problem_heading(problem_name))
+}
+
+/// A summary of the cases of a problem, like `hello  PASS (3/3)  0.12s` with the slowest runtime
+fn compact_line(problem_name: &str, cases: &[CaseRun], failed_any: bool) -> String {
+    let passed = cases.iter().filter(|case| case.passed()).count();
+    let status = match Outcome::of_cases(cases, failed_any) {
+        Outcome::NoSamples => "NO SAMPLES".yellow().bold(),
+        Outcome::Failed => "FAIL".red().bold(),
+        _ => "PASS".green().bold(),
+    };
+    let slowest = cases.iter().map(CaseRun::runtime).max().unwrap_or_default();
+    format!(
+        "{}  {status} ({passed}/{})  {:.2}s",
+        compact_name(problem_name),
+        cases.len(),
+        slowest.as_secs_f64()
+    )
+}
+
+/// Explains why a problem that didn't pass is submitted anyway
+fn forced_submit_warning(cases: &[CaseRun]) -> String {
+    let failed = cases.iter().filter(|case| !case.passed()).count();
+    if cases.is_empty() {
+        "Submitting with 0 cases tested because --force is set".to_string()
+    } else {
+        format!(
+            "Submitting despite {failed} of {} cases failing because --force is set",
+            cases.len()
+        )
+    }
+}
+
+/// The problem name, linked to its statement so it can be reread when a case fails
+fn problem_heading(problem_name: &str) -> String {
+    link::problem_link(problem_name, &problem_name.bold().to_string())
+}
+
+/// Describes the cases that should be printed, and whether the problem failed. Passing zero cases
+/// says nothing, so that counts as failing and only submits with --force.
+fn format_cases(
+    cases: &[CaseRun],
+    io: &[ProblemIO],
+    program_name: &str,
+    time_limit: Option<Duration>,
+    options: &CheckOptions,
+) -> (bool, String) {
+    let mut failed_any = cases.is_empty();
+    let mut case_print = String::new();
+    if cases.is_empty() {
+        writeln!(
+            case_print,
+            "{}",
+            "This problem has no public samples. Add your own test cases with --test-dir, \
+            or submit with --force."
+                .yellow()
+        )
+        .unwrap();
+    }
+    for case in cases {
+        if !case.passed() {
+            failed_any = true;
+        } else if options.quiet {
+            continue;
         }
+        let input = options
+            .show_input
+            .filter(|_| !case.passed())
+            .and_then(|max_lines| {
+                let pio = io.iter().find(|pio| pio.name == case.case_name)?;
+                Some(truncate_lines(&pio.get_input_string().ok()?, max_lines))
+            });
+        case_print.push_str(&case.display(program_name, input.as_deref(), time_limit));
     }
-    info!("Print results");
+    (failed_any, case_print)
+}
+
+/// Submits the program, asking first if `--confirm` is given
+async fn submit_program(
+    problem_name: &str,
+    program: &Program,
+    options: &CheckOptions,
+) -> Result<()> {
+    if options.confirm && !confirm_submission(problem_name, program).await? {
+        eprintln!("Not submitting {}", program.name());
+        return Ok(());
+    }
+    program
+        .submit(
+            problem_name,
+            options.submission_viewer,
+            options.poll,
+            options.dry_run,
+        )
+        .await
+}
 
-    Ok(true)
+/// Asks on the terminal whether to submit the program. Problems are checked concurrently, so
+/// the prompts take turns.
+async fn confirm_submission(problem_name: &str, program: &Program) -> Result<bool> {
+    static PROMPT_LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+    let _guard = PROMPT_LOCK.lock().await;
+
+    // On stderr, so that stdout stays valid JSON with --output json
+    eprint!(
+        "Submit {} ({}) to {}? [y/N] ",
+        program.source.display().to_string().bold(),
+        program.lang,
+        problem_name.bold()
+    );
+    std::io::stderr().flush()?;
+    let answer = tokio::task::spawn_blocking(|| {
+        let mut answer = String::new();
+        std::io::stdin().read_line(&mut answer).map(|_| answer)
+    })
+    .await??;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
 }
 
-fn check_problem_output(pio: &ProblemIO, out: &Output) -> RunResult {
-    #[cfg(unix)]
-    let segfaulted = matches!(&out.status.signal(), Some(11));
+/// Value for the color flags of the compilers, so their errors are only colored when we color too
+fn color_choice() -> &'static str {
+    if use_color() {
+        "always"
+    } else {
+        "never"
+    }
+}
 
-    #[cfg(not(unix))]
-    let segfaulted = {
-        let status = out.status.to_string();
-        let seg_opt = SEGFAULT_RE
-            .captures(&status)
-            .and_then(|cap| cap.get(1).map(|m| m.as_str() == "11"));
-        matches!(seg_opt, Some(true))
+/// Whether an executable with this name can be found in `PATH`
+pub fn in_path(name: &str) -> bool {
+    let Some(path) = std::env::var_os("PATH") else {
+        return false;
     };
+    std::env::split_paths(&path).any(|dir| {
+        dir.join(name).is_file() || (cfg!(windows) && dir.join(format!("{name}.exe")).is_file())
+    })
+}
 
-    if out.status.success() && !segfaulted {
+/// Picks the interpreter for Python programs. `python` is Python 2 or missing on many systems,
+/// so `python3` is preferred.
+async fn python_interpreter() -> Result<String> {
+    if in_path("python3") {
+        return Ok("python3".to_string());
+    }
+    if !in_path("python") {
+        bail!("Couldn't find python3 or python in path. Make sure Python 3 is installed.");
+    }
+    let output = Command::new("python").arg("--version").output().await?;
+    // Python 2 prints its version to stderr
+    let version = format!(
+        "{}{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    if version.trim_start().starts_with("Python 2") {
+        bail!(
+            "python is {}, but Kattis runs Python 3. Install python3 or make python point to Python 3.",
+            version.trim()
+        );
+    }
+    Ok("python".to_string())
+}
+
+/// Keeps the first `max_lines` lines of `text`, noting how many were left out
+fn truncate_lines(text: &str, max_lines: usize) -> String {
+    let lines = text.lines().collect_vec();
+    if lines.len() <= max_lines {
+        return lines.join("\n");
+    }
+    let note = format!("… {} more lines", lines.len() - max_lines)
+        .yellow()
+        .to_string();
+    lines[..max_lines]
+        .iter()
+        .copied()
+        .chain(std::iter::once(note.as_str()))
+        .join("\n")
+}
+
+/// Messages that runtimes print when an allocation fails
+const OUT_OF_MEMORY_MARKERS: &[&str] = &[
+    "std::bad_alloc",
+    "MemoryError",
+    "memory allocation of",
+    "out of memory",
+    "OutOfMemoryError",
+    "OutOfMemoryException",
+];
+
+/// Runs a custom checker as `checker <input> <output> <expected output>`. The checker accepts
+/// the output by exiting successfully.
+async fn run_checker(checker: &Path, pio: &ProblemIO, stdout: &[u8]) -> Result<RunResult> {
+    let mut output_file = fetch::temp_file()?;
+    output_file.write_all(stdout)?;
+    let verdict = interrupt::output(
+        Command::new(checker)
+            .arg(pio.input.as_os_str())
+            .arg(output_file.path())
+            .arg(pio.output.as_os_str()),
+    )
+    .await
+    .with_context(|| format!("Failed to run checker {}", checker.display()))?;
+    let feedback = String::from_utf8_lossy(&verdict.stderr).to_string();
+    Ok(RunResult::Checked(verdict.status.success(), feedback))
+}
+
+/// Describes the signal that killed the program
+#[cfg(unix)]
+fn crash_reason(status: ExitStatus) -> Option<String> {
+    let signal = status.signal()?;
+    let name = match signal {
+        libc::SIGSEGV => "Segmentation fault (SIGSEGV)",
+        libc::SIGABRT => "Aborted (SIGABRT), e.g. by a failed assertion or an uncaught exception",
+        libc::SIGFPE => "Arithmetic error (SIGFPE), e.g. an integer division by zero",
+        libc::SIGILL => "Illegal instruction (SIGILL)",
+        libc::SIGBUS => "Bus error (SIGBUS)",
+        libc::SIGTRAP => "Trace trap (SIGTRAP)",
+        libc::SIGKILL => "Killed (SIGKILL)",
+        libc::SIGTERM => "Terminated (SIGTERM)",
+        libc::SIGPIPE => "Broken pipe (SIGPIPE)",
+        libc::SIGXCPU => "CPU time limit exceeded (SIGXCPU)",
+        libc::SIGXFSZ => "File size limit exceeded (SIGXFSZ)",
+        libc::SIGSYS => "Bad system call (SIGSYS)",
+        _ => return Some(format!("Killed by signal {signal}")),
+    };
+    Some(name.to_string())
+}
+
+/// Describes the NTSTATUS exit code of a program that crashed
+#[cfg(windows)]
+fn crash_reason(status: ExitStatus) -> Option<String> {
+    let name = match status.code()?.cast_unsigned() {
+        0xC000_0005 => "Access violation (segmentation fault)",
+        0xC000_0094 => "Integer division by zero",
+        0xC000_0095 => "Integer overflow",
+        0xC000_00FD => "Stack overflow",
+        0xC000_001D => "Illegal instruction",
+        0xC000_0374 => "Heap corruption",
+        0xC000_0409 => "Aborted (stack buffer overrun or fail fast)",
+        code if code >= 0xC000_0000 => {
+            return Some(format!("Crashed with exception code {code:#X}"))
+        }
+        _ => return None,
+    };
+    Some(name.to_string())
+}
+
+#[cfg(not(any(unix, windows)))]
+const fn crash_reason(_status: ExitStatus) -> Option<String> {
+    None
+}
+
+async fn check_problem_output(
+    pio: &ProblemIO,
+    out: &Output,
+    options: &CheckOptions,
+) -> Result<RunResult> {
+    if out.status.success() && crash_reason(out.status).is_none() {
+        if let Some(checker) = &options.checker {
+            return run_checker(checker, pio, &out.stdout).await;
+        }
         let output_string = from_utf8(out.stdout.as_slice()).unwrap().to_owned();
         let pio_output_string: String = pio.get_output_string().unwrap();
-        let compare_result = compare(&output_string, &pio_output_string);
-        RunResult::Completed(compare_result)
+        let compare_result = compare(&output_string, &pio_output_string, &options.compare);
+        Ok(RunResult::Completed(compare_result))
     } else {
-        let runtime_error = if segfaulted {
-            "Segmentation fault\n".red().to_string()
-        } else {
-            from_utf8(out.stderr.as_slice()).unwrap().to_string()
-        };
+        Ok(runtime_error(out, options.mem_limit.is_some()))
+    }
+}
 
-        let output_before_crash = from_utf8(out.stdout.as_slice()).unwrap();
-        RunResult::RuntimeError(runtime_error, output_before_crash.to_owned())
+/// Result of a program that crashed or exited unsuccessfully
+fn runtime_error(out: &Output, mem_limited: bool) -> RunResult {
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    let runtime_error = match (sanitizer_report(&stderr), crash_reason(out.status)) {
+        (Some(report), _) => report,
+        (None, reason) => plain_runtime_error(out, &stderr, reason),
+    };
+
+    if mem_limited
+        && OUT_OF_MEMORY_MARKERS
+            .iter()
+            .any(|marker| runtime_error.contains(marker))
+    {
+        return RunResult::MemoryLimitExceeded(runtime_error);
     }
+
+    let output_before_crash = String::from_utf8_lossy(&out.stdout);
+    RunResult::RuntimeError(runtime_error, output_before_crash.to_string())
 }
 
-async fn run_problem<'a>(problem: &'a Problem, ios: &'a [ProblemIO]) -> ProblemInstance<'a> {
-    async fn run_submission<'b>(program: &'b Program, ios: &'b [ProblemIO]) -> ProblemInstance<'b> {
+/// The crash reason or exit code along with what the program printed to stderr
+fn plain_runtime_error(out: &Output, stderr: &str, reason: Option<String>) -> String {
+    match reason {
+        Some(reason) => format!("{}\n{stderr}", reason.red()),
+        None if stderr.trim().is_empty() => {
+            let code = out
+                .status
+                .code()
+                .map_or_else(|| "unknown".to_string(), |c| c.to_string());
+            format!("{}\n", format!("Exited with code {code}").red())
+        }
+        None => stderr.to_string(),
+    }
+}
+
+/// Finds an error reported by a sanitizer, like `-fsanitize=address` or `-fsanitize=undefined`
+/// added through `extra_flags`, and puts its summary first. The shadow memory dump after the
+/// summary is left out, and anything the program printed before the report is kept below it.
+fn sanitizer_report(stderr: &str) -> Option<String> {
+    let lines: Vec<&str> = stderr.lines().collect();
+    let start = lines.iter().position(|line| {
+        (line.starts_with("==") && line.contains("Sanitizer")) || line.contains(": runtime error: ")
+    })?;
+    let summary = lines[start..]
+        .iter()
+        .position(|line| line.starts_with("SUMMARY: "))
+        .map(|offset| start + offset);
+
+    let title = if let Some((_, message)) = lines[start].split_once(": runtime error: ") {
+        format!("UndefinedBehaviorSanitizer: {message}")
+    } else if let Some(summary) = summary {
+        lines[summary].trim_start_matches("SUMMARY: ").to_string()
+    } else {
+        lines[start]
+            .split_once("ERROR: ")
+            .map_or(lines[start], |(_, error)| error)
+            .to_string()
+    };
+    let end = summary.map_or(lines.len(), |summary| summary + 1);
+    let mut report = format!("{}\n{}\n", title.red().bold(), lines[start..end].join("\n"));
+    let before: Vec<&str> = lines[..start]
+        .iter()
+        .copied()
+        .filter(|line| !line.chars().all(|c| c == '='))
+        .collect();
+    if !before.is_empty() {
+        write!(
+            report,
+            "{}\n{}\n",
+            "Before the report, stderr had:".dimmed(),
+            before.join("\n")
+        )
+        .unwrap();
+    }
+    Some(report)
+}
+
+async fn run_problem<'a>(
+    problem: &'a Problem,
+    ios: &'a [ProblemIO],
+    options: &CheckOptions,
+) -> Result<ProblemInstance<'a>> {
+    async fn run_submission<'b>(
+        program: &'b Program,
+        ios: &'b [ProblemIO],
+        options: &CheckOptions,
+    ) -> Result<ProblemInstance<'b>> {
         match &program.compiled {
             // Guard against programs that aren't ready to run
             Some(Err(compile_error)) => {
-                return ProblemInstance {
+                return Ok(ProblemInstance {
                     program,
                     result: ProblemInstanceResult::CompileError(compile_error.clone()),
-                }
+                })
             }
             None => {
                 panic!("Program was not attempted compiled (internal error, please report this)")
@@ -625,31 +2267,34 @@ async fn run_problem<'a>(problem: &'a Problem, ios: &'a [ProblemIO]) -> ProblemI
         }
 
         // Stream of results coming from the async functions that are completing
-        let mut result_stream = program.run_problems(ios);
+        let mut result_stream = program.run_problems(ios, options);
 
         let mut results: Vec<CaseRun> = Vec::new();
-        while let Some((pio, out)) = result_stream.try_next().await.unwrap() {
-            let run_result = check_problem_output(pio, &out);
+        while let Some((pio, run_result, mut runtimes)) = result_stream.try_next().await? {
+            runtimes.sort();
             results.push(CaseRun {
                 case_name: pio.name.clone(),
                 run_result,
+                runtimes,
             });
         }
         info!("Starting to run problems");
 
-        ProblemInstance {
+        Ok(ProblemInstance {
             program,
             result: ProblemInstanceResult::Ran(results),
-        }
+        })
     }
 
-    run_submission(&problem.submission, ios).await
+    run_submission(&problem.submission, ios, options).await
 }
 
 #[cfg(test)]
 mod test {
-    use crate::checker::Lang;
+    use crate::checker::{local_dependencies, sanitizer_report, Lang, ProblemSource, Program};
     use enum_iterator::all;
+    use std::fs;
+    use std::path::PathBuf;
 
     #[test]
     fn complete_langs() {
@@ -658,4 +2303,107 @@ mod test {
             assert_eq!(Lang::from_extension(lang.extension()).unwrap(), lang);
         }
     }
+
+    #[test]
+    fn mainclass() {
+        let program = |path: &str| {
+            Program::from_problem_source(ProblemSource {
+                problem_name: "hello".to_string(),
+                path: PathBuf::from(path),
+                lang: Lang::from_extension(&path[path.rfind('.').unwrap() + 1..]).unwrap(),
+            })
+        };
+        assert_eq!(program("hello.kt").mainclass("hello"), "HelloKt");
+        assert_eq!(program("two-sum.kt").mainclass("twosum"), "Two_sumKt");
+        assert_eq!(program("hello.cpp").mainclass("hello"), "hello");
+    }
+
+    #[test]
+    fn local_dependencies_are_followed() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = |name: &str, contents: &str| {
+            let path = dir.path().join(name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, contents).unwrap();
+            path.canonicalize().unwrap()
+        };
+        let source = file(
+            "hello.cpp",
+            "#include <vector>\n#include \"util.h\"\n#include \"missing.h\"\n",
+        );
+        let util = file("util.h", "#pragma once\n#include \"lib/math.h\"\n");
+        let math = file("lib/math.h", "#include \"../util.h\"\n");
+        assert_eq!(local_dependencies(&Lang::Cpp, &source), [util, math]);
+
+        let source = file(
+            "main.rs",
+            "mod parse;\npub(crate) mod graph;\nmod inline {}\n",
+        );
+        let parse = file("parse.rs", "mod lexer;\n");
+        let lexer = file("parse/lexer.rs", "");
+        let graph = file("graph/mod.rs", "");
+        assert_eq!(
+            local_dependencies(&Lang::Rust, &source),
+            [parse, graph, lexer]
+        );
+    }
+
+    #[test]
+    fn sanitizer_reports() {
+        let asan = "debug print\n\
+            =================================================================\n\
+            ==15488==ERROR: AddressSanitizer: heap-buffer-overflow on address 0x602000000028\n\
+            READ of size 4 at 0x602000000028 thread T0\n    #0 0x56211df233d5 in main asan.cpp:3\n\
+            SUMMARY: AddressSanitizer: heap-buffer-overflow asan.cpp:3 in main\n\
+            Shadow bytes around the buggy address:\n\
+            ==15488==ABORTING\n";
+        let report = sanitizer_report(asan).unwrap();
+        assert!(report.contains("AddressSanitizer: heap-buffer-overflow asan.cpp:3 in main"));
+        assert!(report.contains("==15488==ERROR"));
+        assert!(!report.contains("Shadow bytes"));
+        assert!(report.ends_with("\ndebug print\n"));
+
+        let ubsan = "ub.cpp:3:54: runtime error: signed integer overflow: 2147483647 + 1\n";
+        let report = sanitizer_report(ubsan).unwrap();
+        assert!(
+            report.contains("UndefinedBehaviorSanitizer: signed integer overflow: 2147483647 + 1")
+        );
+        assert!(sanitizer_report(
+            "terminate called after throwing an instance of 'std::bad_alloc'"
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn file_input_patterns() {
+        let matches = |lang: Lang, line: &str| {
+            regex::Regex::new(lang.file_input_pattern())
+                .unwrap()
+                .is_match(line)
+        };
+        assert!(matches(Lang::Python, "data = open('input.txt').read()"));
+        assert!(!matches(Lang::Python, "data = open(0).read()"));
+        assert!(matches(Lang::Cpp, "freopen(\"in.txt\", \"r\", stdin);"));
+        assert!(matches(Lang::Cpp, "std::ifstream in(\"1.in\");"));
+        assert!(!matches(Lang::Cpp, "std::cin >> n;"));
+        assert!(matches(
+            Lang::Rust,
+            "let s = fs::read_to_string(\"in\").unwrap();"
+        ));
+        assert!(!matches(
+            Lang::JavaScript,
+            "require(\"fs\").readFileSync(0, \"utf8\")"
+        ));
+        assert!(!matches(Lang::JavaScript, "readFileSync(\"/dev/stdin\")"));
+        assert!(matches(
+            Lang::CSharp,
+            "var lines = File.ReadAllLines(\"in.txt\");"
+        ));
+        assert!(!matches(
+            Lang::CSharp,
+            "var input = Console.In.ReadToEnd();"
+        ));
+        assert!(matches(Lang::Ruby, "lines = File.readlines('in.txt')"));
+        assert!(!matches(Lang::Ruby, "lines = $stdin.readlines"));
+    }
 }
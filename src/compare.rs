@@ -1,13 +1,21 @@
+use clap::builder::PossibleValue;
+use clap::ValueEnum;
 use colored::{ColoredString, Colorize};
 
 use itertools::{EitherOrBoth, Itertools};
 
 use log::info;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet};
 use std::fmt::Formatter;
+use std::io::IsTerminal;
+use std::sync::LazyLock;
 
 use regex::{Captures, Regex};
+use serde::Serialize;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "status", content = "line", rename_all = "snake_case")]
 pub enum LineStatus {
     Wrong(String, String), // Wrong, correction
     Correct(String),       // Correct
@@ -15,68 +23,115 @@ pub enum LineStatus {
     Overpresent(String),   // Line past output
 }
 
+/// How the program output is matched against the expected output, and how differences are shown
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CompareOptions {
+    /// Accept real numbers within this absolute or relative error, instead of rounding them to
+    /// the precision of the expected output
+    pub tolerance: Option<f64>,
+    /// Compare the sequences of whitespace separated tokens rather than line by line
+    pub whitespace_insensitive: bool,
+    /// Accept the lines of the output in any order
+    pub unordered: bool,
+    /// Report missing or extra empty lines at the end of the output
+    pub strict_trailing: bool,
+    /// Number of differing lines to show before truncating the diff. 0 shows every line.
+    pub max_diff_lines: usize,
+    pub diff_style: DiffStyle,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DiffStyle {
+    /// Wrong lines followed by their corrections
+    #[default]
+    Stacked,
+    /// Expected and actual output in two columns
+    SideBySide,
+}
+
+impl ValueEnum for DiffStyle {
+    fn value_variants<'a>() -> &'a [Self] {
+        &[Self::Stacked, Self::SideBySide]
+    }
+
+    fn to_possible_value(&self) -> Option<PossibleValue> {
+        Some(match self {
+            Self::Stacked => {
+                PossibleValue::new("stacked").help("Show wrong lines followed by their corrections")
+            }
+            Self::SideBySide => PossibleValue::new("side-by-side")
+                .help("Show expected and actual output in two columns when writing to a terminal"),
+        })
+    }
+}
+
+#[derive(Serialize)]
 pub struct ComparisonResult {
+    #[serde(rename = "diff")]
     pub failed: Option<Vec<LineStatus>>,
+    /// Set when the output passed but isn't byte for byte equal to the answer
+    #[serde(skip)]
+    whitespace_note: Option<&'static str>,
+    #[serde(skip)]
+    max_diff_lines: usize,
+    #[serde(skip)]
+    diff_style: DiffStyle,
 }
 
 impl ComparisonResult {
-    pub fn new(x: Vec<LineStatus>) -> Self {
+    pub fn new(x: Vec<LineStatus>, options: &CompareOptions) -> Self {
         let failed = if x.iter().all(|x| matches!(x, LineStatus::Correct(_))) {
             None
         } else {
             Some(x)
         };
 
-        Self { failed }
+        Self {
+            failed,
+            whitespace_note: None,
+            max_diff_lines: options.max_diff_lines,
+            diff_style: options.diff_style,
+        }
     }
 }
 
 impl std::fmt::Display for ComparisonResult {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         let output = self.failed.as_ref().map_or_else(
-            || "Success".green().bold().to_string(),
+            || {
+                let note = self
+                    .whitespace_note
+                    .map(|note| format!(" ({note})").dimmed().to_string())
+                    .unwrap_or_default();
+                format!("{}{note}", "Success".green().bold())
+            },
             |failures| {
-                // Group into error blocks
-                let mut correction: Vec<ColoredString> = Vec::new();
-                let it = failures.iter();
-
-                // (error buffer, correction buffer)
-                let mut error_block_buf = (Vec::new(), Vec::new());
-                for inner in it {
-                    match inner {
-                        LineStatus::Wrong(wrong_line, correction) => {
-                            if wrong_line.is_empty() {
-                                error_block_buf.0.push(" ".on_red());
-                            } else {
-                                error_block_buf.0.push(wrong_line.red());
-                            }
-                            error_block_buf.1.push(correction.green());
-                        }
-                        LineStatus::Correct(correct_line) => {
-                            correction.append(&mut error_block_buf.0);
-                            correction.append(&mut error_block_buf.1);
-                            error_block_buf.0.clear();
-                            error_block_buf.1.clear();
-                            correction.push(correct_line.white());
-                        }
-                        LineStatus::Missing(missing_line) => {
-                            error_block_buf.0.push(" ".on_red());
-                            error_block_buf.1.push(missing_line.green());
-                        }
-                        LineStatus::Overpresent(overpresent_line) => {
-                            error_block_buf.0.push({
-                                if overpresent_line.is_empty() {
-                                    " ".on_red()
-                                } else {
-                                    overpresent_line.red()
-                                }
-                            });
-                        }
-                    }
-                }
-                if !error_block_buf.0.is_empty() {
-                    correction.append(&mut error_block_buf.0);
-                    correction.append(&mut error_block_buf.1);
+                // Only show up to max_diff_lines differing lines
+                let is_diff = |x: &&LineStatus| !matches!(x, LineStatus::Correct(_));
+                let total_diffs = failures.iter().filter(is_diff).count();
+                let hidden_diffs = if self.max_diff_lines == 0 {
+                    0
+                } else {
+                    total_diffs.saturating_sub(self.max_diff_lines)
+                };
+                let shown = failures
+                    .iter()
+                    .positions(|x| is_diff(&x))
+                    .nth(self.max_diff_lines)
+                    .filter(|_| hidden_diffs > 0)
+                    .map_or(failures.as_slice(), |end| &failures[..end]);
+
+                // Columns can only be aligned in a terminal
+                let mut correction = if self.diff_style == DiffStyle::SideBySide
+                    && std::io::stdout().is_terminal()
+                {
+                    side_by_side(shown, terminal_width())
+                } else {
+                    stacked(shown)
+                };
+
+                if hidden_diffs > 0 {
+                    correction.push(format!("… {hidden_diffs} more differing lines").yellow());
                 }
 
                 correction.into_iter().map(|cs| cs.to_string()).join("\n")
@@ -86,9 +141,157 @@ impl std::fmt::Display for ComparisonResult {
     }
 }
 
-lazy_static::lazy_static! {
-    static ref RE: Regex = Regex::new(r"([-+]?[0-9]+)(\.([0-9]+))?").unwrap();
+/// Shows each block of wrong lines followed by its correction
+fn stacked(lines: &[LineStatus]) -> Vec<ColoredString> {
+    // Group into error blocks
+    let mut correction: Vec<ColoredString> = Vec::new();
+    let it = lines.iter();
+
+    // (error buffer, correction buffer)
+    let mut error_block_buf = (Vec::new(), Vec::new());
+    for inner in it {
+        match inner {
+            LineStatus::Wrong(wrong_line, correction) => {
+                if wrong_line.is_empty() {
+                    error_block_buf.0.push(" ".on_red());
+                    error_block_buf.1.push(correction.green());
+                } else if let Some((wrong_line, correction)) =
+                    highlight_tokens(wrong_line, correction)
+                {
+                    error_block_buf.0.push(wrong_line.normal());
+                    error_block_buf.1.push(correction.normal());
+                } else {
+                    error_block_buf.0.push(wrong_line.red());
+                    error_block_buf.1.push(correction.green());
+                }
+            }
+            LineStatus::Correct(correct_line) => {
+                correction.append(&mut error_block_buf.0);
+                correction.append(&mut error_block_buf.1);
+                error_block_buf.0.clear();
+                error_block_buf.1.clear();
+                correction.push(correct_line.white());
+            }
+            LineStatus::Missing(missing_line) => {
+                error_block_buf.0.push(" ".on_red());
+                error_block_buf.1.push(missing_line.green());
+            }
+            LineStatus::Overpresent(overpresent_line) => {
+                error_block_buf.0.push({
+                    if overpresent_line.is_empty() {
+                        " ".on_red()
+                    } else {
+                        overpresent_line.red()
+                    }
+                });
+            }
+        }
+    }
+    if !error_block_buf.0.is_empty() {
+        correction.append(&mut error_block_buf.0);
+        correction.append(&mut error_block_buf.1);
+    }
+    correction
 }
+
+/// Colors only the tokens that differ when the lines have the same number of tokens, so a single
+/// wrong number stands out
+fn highlight_tokens(wrong_line: &str, correction: &str) -> Option<(String, String)> {
+    static TOKEN_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\S+|\s+").unwrap());
+    let tokens = |line: &str| -> Vec<String> {
+        TOKEN_RE
+            .find_iter(line)
+            .map(|m| m.as_str().to_string())
+            .collect()
+    };
+    let (wrong_tokens, correction_tokens) = (tokens(wrong_line), tokens(correction));
+    let words = |tokens: &[String]| tokens.iter().filter(|t| !t.trim().is_empty()).count();
+    if words(&wrong_tokens) != words(&correction_tokens) {
+        return None;
+    }
+
+    let differs: Vec<bool> = wrong_tokens
+        .iter()
+        .filter(|t| !t.trim().is_empty())
+        .zip(correction_tokens.iter().filter(|t| !t.trim().is_empty()))
+        .map(|(w, c)| !line_eq(w, c))
+        .collect();
+    let render = |tokens: &[String], color: fn(&str) -> ColoredString| -> String {
+        let mut word = 0;
+        tokens
+            .iter()
+            .map(|token| {
+                if token.trim().is_empty() {
+                    return token.clone();
+                }
+                word += 1;
+                if differs[word - 1] {
+                    color(token).bold().to_string()
+                } else {
+                    token.white().to_string()
+                }
+            })
+            .collect()
+    };
+    Some((
+        render(&wrong_tokens, |t| t.red()),
+        render(&correction_tokens, |t| t.green()),
+    ))
+}
+
+/// Shows the expected and actual output in two columns that fit within `width`
+fn side_by_side(lines: &[LineStatus], width: usize) -> Vec<ColoredString> {
+    let column_width = width.saturating_sub(3) / 2;
+    let fit = |line: &str| -> String {
+        if line.chars().count() > column_width {
+            let truncated: String = line.chars().take(column_width.saturating_sub(1)).collect();
+            format!("{truncated}…")
+        } else {
+            format!("{line:<column_width$}")
+        }
+    };
+
+    let mut rendered =
+        vec![format!("{} │ {}", fit("Expected").bold(), fit("Output").bold()).normal()];
+    for status in lines {
+        let (expected, actual) = match status {
+            LineStatus::Correct(line) => (fit(line).white(), fit(line).white()),
+            LineStatus::Wrong(wrong_line, correction) => {
+                (fit(correction).green(), fit(wrong_line).red())
+            }
+            LineStatus::Missing(missing_line) => (fit(missing_line).green(), fit("").normal()),
+            LineStatus::Overpresent(overpresent_line) => {
+                (fit("").normal(), fit(overpresent_line).red())
+            }
+        };
+        rendered.push(format!("{expected} │ {actual}").normal());
+    }
+    rendered
+}
+
+/// Width of the terminal that stdout is attached to, falling back to `$COLUMNS` or 80
+pub fn terminal_width() -> usize {
+    #[cfg(unix)]
+    {
+        let mut size = libc::winsize {
+            ws_row: 0,
+            ws_col: 0,
+            ws_xpixel: 0,
+            ws_ypixel: 0,
+        };
+        // SAFETY: TIOCGWINSZ only writes a winsize to the given pointer
+        let res = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &raw mut size) };
+        if res == 0 && size.ws_col > 0 {
+            return usize::from(size.ws_col);
+        }
+    }
+    std::env::var("COLUMNS")
+        .ok()
+        .and_then(|columns| columns.parse().ok())
+        .unwrap_or(80)
+}
+
+static RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"([-+]?[0-9]+)(\.([0-9]+))?").unwrap());
 fn line_eq(text: &str, key: &str) -> bool {
     // Round real numbers properly
     let mut key_iter = RE.captures_iter(key);
@@ -106,47 +309,365 @@ fn line_eq(text: &str, key: &str) -> bool {
     rounded.eq(key)
 }
 
-fn compare_lines(text: &str, key: &str) -> LineStatus {
+fn line_eq_within(text: &str, key: &str, tolerance: f64) -> bool {
+    let token_eq = |(t, k): (&str, &str)| {
+        t == k
+            || match (t.parse::<f64>(), k.parse::<f64>()) {
+                (Ok(t), Ok(k)) => {
+                    let error = (t - k).abs();
+                    error <= tolerance || error <= tolerance * k.abs()
+                }
+                _ => false,
+            }
+    };
+    let text_tokens = text.split_whitespace();
+    let key_tokens = key.split_whitespace();
+    text_tokens.clone().count() == key_tokens.clone().count()
+        && text_tokens.zip(key_tokens).all(token_eq)
+}
+
+fn matches(text: &str, key: &str, options: &CompareOptions) -> bool {
+    options.tolerance.map_or_else(
+        || line_eq(text, key),
+        |tolerance| line_eq_within(text, key, tolerance),
+    )
+}
+
+fn compare_lines(text: &str, key: &str, options: &CompareOptions) -> LineStatus {
+    // Whitespace around the line is ignored, like on Kattis. Lines that only differ there are
+    // pointed out by `whitespace_note` instead.
     const TO_STRIP: &[char] = &['\n', ' ', '\t', '\r'];
-    let pat = |c| TO_STRIP.contains(&c);
-    let orig = text.trim_matches(pat).trim_matches(pat);
-    let other = key.trim_matches(pat).trim_matches(pat);
+    let orig = text.trim_matches(TO_STRIP);
+    let other = key.trim_matches(TO_STRIP);
 
-    if line_eq(orig, other) {
+    if matches(orig, other, options) {
         LineStatus::Correct(orig.to_string())
     } else {
         LineStatus::Wrong(orig.to_string(), other.to_string())
     }
 }
 
-pub fn compare(output: &str, key: &str) -> ComparisonResult {
+/// Compares the token sequences of `output` and `key`, and reports the results for the lines
+/// that the tokens came from
+fn compare_tokens(output: &str, key: &str, options: &CompareOptions) -> Vec<LineStatus> {
+    use EitherOrBoth::{Both, Left, Right};
+
+    let output_lines: Vec<&str> = output.lines().collect();
+    let key_lines: Vec<&str> = key.lines().collect();
+    let tokens = |lines: &[&str]| -> Vec<(usize, String)> {
+        lines
+            .iter()
+            .enumerate()
+            .flat_map(|(i, line)| line.split_whitespace().map(move |t| (i, t.to_string())))
+            .collect()
+    };
+
+    // Output line -> key line of its first mismatching token
+    let mut wrong_lines: BTreeMap<usize, usize> = BTreeMap::new();
+    let mut overpresent_lines: BTreeSet<usize> = BTreeSet::new();
+    let mut missing_lines: BTreeSet<usize> = BTreeSet::new();
+    for pair in tokens(&output_lines)
+        .into_iter()
+        .zip_longest(tokens(&key_lines))
+    {
+        match pair {
+            Both((o_line, o), (k_line, k)) => {
+                if !matches(&o, &k, options) {
+                    wrong_lines.entry(o_line).or_insert(k_line);
+                }
+            }
+            Left((o_line, _)) => {
+                overpresent_lines.insert(o_line);
+            }
+            Right((k_line, _)) => {
+                missing_lines.insert(k_line);
+            }
+        }
+    }
+
+    let mut comparisons: Vec<LineStatus> = output_lines
+        .iter()
+        .enumerate()
+        .map(|(i, line)| {
+            if let Some(&k_line) = wrong_lines.get(&i) {
+                LineStatus::Wrong((*line).to_string(), key_lines[k_line].to_string())
+            } else if overpresent_lines.contains(&i) {
+                LineStatus::Overpresent((*line).to_string())
+            } else {
+                LineStatus::Correct((*line).to_string())
+            }
+        })
+        .collect();
+    let shown_key_lines: BTreeSet<usize> = wrong_lines.into_values().collect();
+    comparisons.extend(
+        missing_lines
+            .difference(&shown_key_lines)
+            .map(|&k_line| LineStatus::Missing(key_lines[k_line].to_string())),
+    );
+    comparisons
+}
+
+/// Compares the sorted lines of `output` and `key`, so that lines that are actually missing or
+/// extra are reported rather than every line after them
+fn compare_unordered(output: &str, key: &str, options: &CompareOptions) -> Vec<LineStatus> {
+    use EitherOrBoth::{Both, Left, Right};
+
+    let sorted_lines = |text: &str| -> Vec<String> {
+        text.lines()
+            .map(|line| {
+                if options.whitespace_insensitive {
+                    line.split_whitespace().join(" ")
+                } else {
+                    line.trim().to_string()
+                }
+            })
+            .filter(|line| !line.is_empty())
+            .sorted()
+            .collect()
+    };
+
+    sorted_lines(output)
+        .into_iter()
+        .merge_join_by(sorted_lines(key), |o, k| {
+            if matches(o, k, options) {
+                Ordering::Equal
+            } else {
+                o.cmp(k)
+            }
+        })
+        .map(|out_key| match out_key {
+            Both(o, _) => LineStatus::Correct(o),
+            Left(o) => LineStatus::Overpresent(o),
+            Right(k) => LineStatus::Missing(k),
+        })
+        .collect()
+}
+
+pub fn compare(output: &str, key: &str, options: &CompareOptions) -> ComparisonResult {
     use EitherOrBoth::{Both, Left, Right};
 
     info!("Starting comparison");
+    // Samples written on Windows end their lines with \r\n
+    let output = &output.replace("\r\n", "\n");
+    let key = &key.replace("\r\n", "\n");
+    if options.unordered || options.whitespace_insensitive {
+        let comparisons = if options.unordered {
+            compare_unordered(output, key, options)
+        } else {
+            compare_tokens(output, key, options)
+        };
+        info!("Finished comparison");
+        return ComparisonResult::new(comparisons, options);
+    }
     let comparisons: Vec<_> = output
         .split('\n')
         .zip_longest(key.split('\n'))
         .filter_map(|out_key| match out_key {
-            Both(o, k) => Some(compare_lines(o, k)),
-            Right(k) if !k.is_empty() => Some(LineStatus::Missing(k.to_string())),
-            Left(o) if !o.is_empty() => Some(LineStatus::Overpresent(o.to_string())),
+            Both(o, k) => Some(compare_lines(o, k, options)),
+            Right(k) if !k.trim().is_empty() || options.strict_trailing => {
+                Some(LineStatus::Missing(k.to_string()))
+            }
+            Left(o) if !o.trim().is_empty() || options.strict_trailing => {
+                Some(LineStatus::Overpresent(o.to_string()))
+            }
             _ => None,
         })
         .collect();
 
     info!("Finished comparison");
-    ComparisonResult::new(comparisons)
+    let mut result = ComparisonResult::new(comparisons, options);
+    if result.failed.is_none() && output != key {
+        result.whitespace_note = whitespace_note(output, key);
+    }
+    result
+}
+
+/// Describes how an accepted output differs from the answer, if it only differs in whitespace.
+/// Kattis accepts these too, but the bytes aren't the same as the answer.
+fn whitespace_note(output: &str, key: &str) -> Option<&'static str> {
+    fn without_trailing(text: &str) -> Vec<&str> {
+        let mut lines = text.lines().map(str::trim_end).collect_vec();
+        while lines.last().is_some_and(|line| line.is_empty()) {
+            lines.pop();
+        }
+        lines
+    }
+    if without_trailing(output) == without_trailing(key) {
+        Some("differs only in trailing whitespace")
+    } else if output.split_whitespace().eq(key.split_whitespace()) {
+        Some("differs only in whitespace")
+    } else {
+        None
+    }
 }
 
 #[cfg(test)]
 mod test {
-    use crate::compare::compare;
+    use crate::compare::{compare, highlight_tokens, CompareOptions, LineStatus};
+    use itertools::Itertools;
 
     #[test]
     fn test_compare() {
         let output = "This is my long story about going to taco bell.\nOne day I felt like I really wanted some good stuff.\nI walked to taco bell to get 0.55512312412345 tacos.".to_string();
         let key = "This is my long story about going to cracko bell.\nOne day I felt like I really wanted some good stuff.\nI walked to cracko bell to get 0.5551231241234 crack.".to_string();
-        let comparisons = compare(&output, &key);
+        let comparisons = compare(&output, &key, &CompareOptions::default());
         println!("{comparisons}");
     }
+
+    #[test]
+    fn test_compare_crlf() {
+        let options = CompareOptions::default();
+        let key = "1 2\r\n3\r\n\r\n";
+        assert!(compare("1 2\n3\n", key, &options).failed.is_none());
+        assert!(compare("1 2\r\n3", "1 2\n3\n", &options).failed.is_none());
+        let failed = compare("1 2\n", key, &options).failed.unwrap();
+        assert_eq!(
+            failed
+                .iter()
+                .filter(|l| !matches!(l, LineStatus::Correct(_)))
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_compare_trailing_spaces() {
+        let options = CompareOptions::default();
+        let result = compare("1 2  \n3\t\n", "1 2\n3\n", &options);
+        assert!(result.failed.is_none());
+        assert_eq!(
+            result.whitespace_note,
+            Some("differs only in trailing whitespace")
+        );
+        let result = compare(" 1 2\n3\n  \n", "1 2\n3\n", &options);
+        assert!(result.failed.is_none());
+        assert_eq!(result.whitespace_note, Some("differs only in whitespace"));
+        assert_eq!(compare("1 2\n", "1 2\n", &options).whitespace_note, None);
+        assert!(compare("1 2\n", "1  2\n", &options).failed.is_some());
+    }
+
+    #[test]
+    fn test_compare_strict_trailing() {
+        let strict = CompareOptions {
+            strict_trailing: true,
+            ..CompareOptions::default()
+        };
+        assert!(compare("1\n2\n\n", "1\n2\n", &CompareOptions::default())
+            .failed
+            .is_none());
+        assert!(compare("1\n2", "1\n2\n", &CompareOptions::default())
+            .failed
+            .is_none());
+        assert!(compare("1\n2\n", "1\n2\n", &strict).failed.is_none());
+        assert!(compare("1\n2\n\n", "1\n2\n", &strict).failed.is_some());
+        assert!(compare("1\n2", "1\n2\n", &strict).failed.is_some());
+        // A blank line at the end of the answer counts too
+        assert!(compare("1\n2\n", "1\n2\n\n", &strict).failed.is_some());
+        assert!(compare("1\n2\n", "1\n2\n\n", &CompareOptions::default())
+            .failed
+            .is_none());
+    }
+
+    #[test]
+    fn test_compare_blank_lines() {
+        let options = CompareOptions::default();
+        // Blank lines between other lines always matter
+        assert!(compare("1\n2\n", "1\n\n2\n", &options).failed.is_some());
+        assert!(compare("1\n\n2\n", "1\n2\n", &options).failed.is_some());
+        assert!(compare("1\n\n2\n", "1\n\n2\n", &options).failed.is_none());
+        // Ignored final blank lines are still pointed out
+        assert_eq!(
+            compare("1\n2\n", "1\n2\n\n", &options).whitespace_note,
+            Some("differs only in trailing whitespace")
+        );
+    }
+
+    #[test]
+    fn test_highlight_tokens() {
+        let colors = regex::Regex::new("\x1b\\[[0-9;]*m").unwrap();
+        let strip_colors = |s: String| colors.replace_all(&s, "").to_string();
+        let (wrong, correction) = highlight_tokens("1 42  3", "1 24  3").unwrap();
+        assert_eq!(strip_colors(wrong), "1 42  3");
+        assert_eq!(strip_colors(correction), "1 24  3");
+        assert!(highlight_tokens("1 2", "1 2 3").is_none());
+    }
+
+    #[test]
+    fn test_compare_tolerance() {
+        let options = CompareOptions {
+            tolerance: Some(1e-6),
+            ..CompareOptions::default()
+        };
+        let key = "0.333333333 1000000.5\n2";
+        assert!(compare("0.3333334 1000000.6\n2", key, &options)
+            .failed
+            .is_none());
+        assert!(compare("0.3334 1000000.5\n2", key, &options)
+            .failed
+            .is_some());
+        assert!(compare("0.333333333\n2", key, &options).failed.is_some());
+        // Rounding to the precision of the key is the default
+        assert!(
+            compare("0.3333334 1000000.5\n2", key, &CompareOptions::default())
+                .failed
+                .is_some()
+        );
+    }
+
+    #[test]
+    fn test_compare_whitespace_insensitive() {
+        let options = CompareOptions {
+            whitespace_insensitive: true,
+            ..CompareOptions::default()
+        };
+        let key = "1 2 3\n4 5\n";
+        assert!(compare("1  2 3 \n4\t5", key, &options).failed.is_none());
+        assert!(compare("1 2\n3 4 5\n\n", key, &options).failed.is_none());
+        assert!(compare("1 2 3\n4 6", key, &options).failed.is_some());
+        assert!(compare("1 2 3\n4", key, &options).failed.is_some());
+        assert!(compare("1  2 3\n4 5", key, &CompareOptions::default())
+            .failed
+            .is_some());
+    }
+
+    #[test]
+    fn test_compare_unordered() {
+        let options = CompareOptions {
+            unordered: true,
+            ..CompareOptions::default()
+        };
+        let key = "apple\nbanana\ncherry\n";
+        assert!(compare("cherry\napple\nbanana\n", key, &options)
+            .failed
+            .is_none());
+
+        let result = compare("cherry\ndurian\napple\n", key, &options);
+        let failed = result.failed.unwrap();
+        assert_eq!(
+            failed
+                .iter()
+                .filter(|l| !matches!(l, LineStatus::Correct(_)))
+                .count(),
+            2
+        );
+        assert!(failed
+            .iter()
+            .any(|l| matches!(l, LineStatus::Missing(k) if k == "banana")));
+        assert!(failed
+            .iter()
+            .any(|l| matches!(l, LineStatus::Overpresent(o) if o == "durian")));
+    }
+
+    #[test]
+    fn test_max_diff_lines() {
+        let options = CompareOptions {
+            max_diff_lines: 2,
+            ..CompareOptions::default()
+        };
+        let output = (0..10).map(|i| i.to_string()).join("\n");
+        let key = (0..10).map(|i| (i * 2).to_string()).join("\n");
+        let rendered = compare(&output, &key, &options).to_string();
+        assert!(rendered.contains("… 7 more differing lines"));
+        assert!(!rendered.contains("18"));
+    }
 }
@@ -0,0 +1,240 @@
+use crate::checker::Lang;
+use clap::{Arg, Command, ValueHint};
+use enum_iterator::all;
+use itertools::Itertools;
+use std::fmt::Write as _;
+
+const BIN_NAME: &str = env!("CARGO_BIN_NAME");
+
+/// Shells that completion scripts can be generated for
+pub const SHELLS: [&str; 3] = ["bash", "zsh", "fish"];
+
+/// Builds a completion script for `shell` from the flags and subcommands of `cmd`
+pub fn generate(shell: &str, mut cmd: Command) -> String {
+    cmd.build();
+    match shell {
+        "bash" => bash(&cmd),
+        "zsh" => zsh(&cmd),
+        "fish" => fish(&cmd),
+        _ => unreachable!("Unsupported shell {shell}"),
+    }
+}
+
+fn flags(cmd: &Command) -> impl Iterator<Item = &Arg> {
+    cmd.get_arguments().filter(|arg| !arg.is_positional())
+}
+
+fn takes_value(arg: &Arg) -> bool {
+    arg.get_action().takes_values()
+}
+
+fn possible_values(arg: &Arg) -> Vec<String> {
+    arg.get_possible_values()
+        .iter()
+        .filter(|value| !value.is_hide_set())
+        .map(|value| value.get_name().to_string())
+        .collect()
+}
+
+/// The first sentence of the help text, which is all that fits in a completion menu
+fn short_help(arg: &Arg) -> String {
+    let help = arg.get_help().map(ToString::to_string).unwrap_or_default();
+    let first_line = help.lines().next().unwrap_or_default();
+    first_line
+        .split_once(". ")
+        .map_or(first_line, |(sentence, _)| sentence)
+        .trim_end_matches('.')
+        .to_string()
+}
+
+fn source_extensions() -> Vec<&'static str> {
+    all::<Lang>().map(|lang| lang.extension()).collect()
+}
+
+fn bash(cmd: &Command) -> String {
+    let mut value_cases = String::new();
+    for arg in flags(cmd).filter(|arg| takes_value(arg)) {
+        let names = arg
+            .get_long_and_visible_aliases()
+            .into_iter()
+            .flatten()
+            .map(|long| format!("--{long}"))
+            .chain(arg.get_short().map(|short| format!("-{short}")))
+            .join("|");
+        let values = possible_values(arg);
+        let reply = match arg.get_value_hint() {
+            _ if !values.is_empty() => {
+                format!("$(compgen -W \"{}\" -- \"$cur\")", values.join(" "))
+            }
+            ValueHint::DirPath => "$(compgen -d -- \"$cur\")".to_string(),
+            ValueHint::FilePath | ValueHint::ExecutablePath | ValueHint::AnyPath => {
+                "$(compgen -f -- \"$cur\")".to_string()
+            }
+            _ => String::new(),
+        };
+        writeln!(
+            value_cases,
+            "        {names})\n            COMPREPLY=({reply})\n            return\n            ;;"
+        )
+        .unwrap();
+    }
+
+    let flag_words = flags(cmd)
+        .flat_map(|arg| {
+            let long = arg.get_long().map(|long| format!("--{long}"));
+            let short = arg.get_short().map(|short| format!("-{short}"));
+            long.into_iter().chain(short)
+        })
+        .join(" ");
+    let subcommands = cmd.get_subcommands().map(Command::get_name).join(" ");
+    let extensions = source_extensions().join(" ");
+
+    format!(
+        r#"_{BIN_NAME}() {{
+    local cur prev
+    cur="${{COMP_WORDS[COMP_CWORD]}}"
+    prev="${{COMP_WORDS[COMP_CWORD-1]}}"
+
+    case "$prev" in
+{value_cases}    esac
+
+    if [[ "$cur" == -* ]]; then
+        COMPREPLY=($(compgen -W "{flag_words}" -- "$cur"))
+        return
+    fi
+
+    local ext
+    COMPREPLY=($(compgen -W "{subcommands}" -- "$cur") $(compgen -d -- "$cur"))
+    for ext in {extensions}; do
+        COMPREPLY+=($(compgen -G "$cur*.$ext"))
+    done
+}}
+
+complete -o filenames -F _{BIN_NAME} {BIN_NAME}
+"#
+    )
+}
+
+/// Escapes text for use inside a single quoted `_arguments` spec
+fn zsh_escape(text: &str) -> String {
+    text.replace('\'', "'\\''")
+        .replace('[', "\\[")
+        .replace(']', "\\]")
+        .replace(':', "\\:")
+}
+
+fn zsh(cmd: &Command) -> String {
+    let mut specs = String::new();
+    for arg in flags(cmd) {
+        let long = arg.get_long().map(|long| format!("--{long}"));
+        let short = arg.get_short().map(|short| format!("-{short}"));
+        let names = long.iter().chain(&short).collect_vec();
+        let name_spec = match names.as_slice() {
+            [name] => (*name).clone(),
+            names => format!(
+                "'({0})'{{{1}}}",
+                names.iter().join(" "),
+                names.iter().join(",")
+            ),
+        };
+        let mut spec = format!("'[{}]", zsh_escape(&short_help(arg)));
+        if takes_value(arg) {
+            let value_name = arg
+                .get_value_names()
+                .and_then(|names| names.first())
+                .map_or_else(|| "VALUE".to_string(), ToString::to_string);
+            let values = possible_values(arg);
+            let action = match arg.get_value_hint() {
+                _ if !values.is_empty() => format!("({})", values.join(" ")),
+                ValueHint::DirPath => "_files -/".to_string(),
+                ValueHint::FilePath | ValueHint::ExecutablePath | ValueHint::AnyPath => {
+                    "_files".to_string()
+                }
+                _ => " ".to_string(),
+            };
+            write!(spec, ":{value_name}:{action}").unwrap();
+        }
+        spec.push('\'');
+        writeln!(specs, "        {name_spec}{spec} \\").unwrap();
+    }
+
+    let subcommands = cmd
+        .get_subcommands()
+        .map(|sub| {
+            let about = sub.get_about().map(ToString::to_string).unwrap_or_default();
+            format!(
+                "{}\\:\"{}\"",
+                sub.get_name(),
+                zsh_escape(about.trim_end_matches('.'))
+            )
+        })
+        .join(" ");
+    let extensions = source_extensions().join("|");
+
+    format!(
+        r#"#compdef {BIN_NAME}
+
+_{BIN_NAME}_sources() {{
+    _alternative \
+        'subcommands:subcommand:(({subcommands}))' \
+        'files:source file:_files -g "*.({extensions})"'
+}}
+
+_{BIN_NAME}() {{
+    _arguments -s \
+{specs}        '*:source file:_{BIN_NAME}_sources'
+}}
+
+_{BIN_NAME} "$@"
+"#
+    )
+}
+
+fn fish(cmd: &Command) -> String {
+    let escape = |text: &str| text.replace('\\', "\\\\").replace('\'', "\\'");
+    let mut script = String::new();
+    for arg in flags(cmd) {
+        let mut line = format!("complete -c {BIN_NAME}");
+        if let Some(short) = arg.get_short() {
+            write!(line, " -s {short}").unwrap();
+        }
+        if let Some(long) = arg.get_long() {
+            write!(line, " -l {long}").unwrap();
+        }
+        write!(line, " -d '{}'", escape(&short_help(arg))).unwrap();
+        if takes_value(arg) {
+            let values = possible_values(arg);
+            match arg.get_value_hint() {
+                _ if !values.is_empty() => write!(line, " -x -a '{}'", values.join(" ")).unwrap(),
+                ValueHint::DirPath => line.push_str(" -x -a '(__fish_complete_directories)'"),
+                ValueHint::FilePath | ValueHint::ExecutablePath | ValueHint::AnyPath => {
+                    line.push_str(" -r -F");
+                }
+                _ => line.push_str(" -x"),
+            }
+        }
+        writeln!(script, "{line}").unwrap();
+    }
+
+    for sub in cmd.get_subcommands() {
+        let about = sub.get_about().map(ToString::to_string).unwrap_or_default();
+        writeln!(
+            script,
+            "complete -c {BIN_NAME} -n __fish_use_subcommand -f -a {} -d '{}'",
+            sub.get_name(),
+            escape(about.trim_end_matches('.'))
+        )
+        .unwrap();
+    }
+
+    let suffixes = source_extensions()
+        .iter()
+        .map(|ext| format!("__fish_complete_suffix .{ext}"))
+        .join("; ");
+    writeln!(
+        script,
+        "complete -c {BIN_NAME} -k -a '(begin; {suffixes}; end)'"
+    )
+    .unwrap();
+    script
+}
@@ -0,0 +1,279 @@
+use crate::checker::Lang;
+use anyhow::{anyhow, bail, Context, Result};
+use clap::Command;
+use enum_iterator::all;
+use log::{info, warn};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+pub const CONFIG_FILE_NAME: &str = ".kattis-rs.toml";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    Scalar(String),
+    Array(Vec<String>),
+}
+
+/// Settings read from `.kattis-rs.toml` files. Only the part of TOML needed for flat tables of
+/// strings, numbers, booleans and arrays is supported.
+#[derive(Debug, Default)]
+pub struct Config {
+    /// Keys before the first table, used as defaults for the flags with the same name
+    pub defaults: BTreeMap<String, Value>,
+    /// Directory with `template.<ext>` files for the `new` subcommand
+    pub template_dir: Option<PathBuf>,
+    /// Flags added to the compiler invocation, from the `extra_flags` of the language's table
+    extra_flags: BTreeMap<String, Vec<String>>,
+}
+
+impl Config {
+    /// Reads the config in the home directory and then the ones from the filesystem root down to
+    /// the working directory, so that the closest file takes precedence
+    pub fn load() -> Result<Self> {
+        let mut paths: Vec<PathBuf> = std::env::current_dir()?
+            .ancestors()
+            .map(|dir| dir.join(CONFIG_FILE_NAME))
+            .collect();
+        if let Some(home) = dirs::home_dir().map(|home| home.join(CONFIG_FILE_NAME)) {
+            if !paths.contains(&home) {
+                paths.push(home);
+            }
+        }
+
+        let mut config = Self::default();
+        for path in paths.iter().rev().filter(|path| path.is_file()) {
+            info!("Reading config from {}", path.display());
+            let contents = std::fs::read_to_string(path)?;
+            config
+                .merge(&contents, path.parent().unwrap())
+                .with_context(|| format!("Failed to read config file {}", path.display()))?;
+        }
+        Ok(config)
+    }
+
+    /// Adds the settings in `contents` on top of the current ones
+    fn merge(&mut self, contents: &str, dir: &Path) -> Result<()> {
+        let mut table: Option<String> = None;
+        for (line_number, line) in contents.lines().enumerate() {
+            let line = strip_comment(line).trim();
+            if line.is_empty() {
+                continue;
+            }
+            let context = || format!("Invalid line {}: {line}", line_number + 1);
+
+            if let Some(name) = line.strip_prefix('[') {
+                let name = name.strip_suffix(']').ok_or_else(|| anyhow!(context()))?;
+                let name = unquote(name.trim()).with_context(context)?;
+                table = Some(name);
+                match all::<Lang>().find(|lang| Some(lang.config_name()) == table.as_deref()) {
+                    Some(lang) if !lang.compiled() => {
+                        warn!(
+                            "{lang} is not compiled, so [{}] in {CONFIG_FILE_NAME} has no effect.",
+                            lang.config_name()
+                        );
+                    }
+                    Some(_) => {}
+                    None => warn!(
+                        "Unknown table [{}] in {CONFIG_FILE_NAME}. Ignoring it.",
+                        table.as_ref().unwrap()
+                    ),
+                }
+                continue;
+            }
+
+            let (key, value) = line.split_once('=').ok_or_else(|| anyhow!(context()))?;
+            let key = unquote(key.trim()).with_context(context)?;
+            let value = parse_value(value.trim()).with_context(context)?;
+            if let Some(table) = &table {
+                if key != "extra_flags" {
+                    warn!("Unknown setting {key} in [{table}] of {CONFIG_FILE_NAME}. Ignoring it.");
+                    continue;
+                }
+                let Value::Array(flags) = value else {
+                    bail!("{}: expected an array of flags", context());
+                };
+                self.extra_flags.insert(table.clone(), flags);
+                continue;
+            }
+            if key == "template-dir" {
+                let Value::Scalar(template_dir) = value else {
+                    bail!("{}: expected a path", context());
+                };
+                self.template_dir = Some(dir.join(template_dir));
+            } else {
+                self.defaults.insert(key, value);
+            }
+        }
+        Ok(())
+    }
+
+    /// Extra compiler flags for programs in `lang`
+    pub fn extra_flags(&self, lang: &Lang) -> Vec<String> {
+        self.extra_flags
+            .get(lang.config_name())
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Uses the top level settings as defaults for the flags of `cmd`, so that flags given on the
+    /// command line still take precedence
+    pub fn apply_defaults(&self, mut cmd: Command) -> Command {
+        for (key, value) in &self.defaults {
+            let Some(id) = cmd
+                .get_arguments()
+                .find(|arg| arg.get_long() == Some(key.as_str()))
+                .map(|arg| arg.get_id().clone())
+            else {
+                warn!("Unknown setting {key} in {CONFIG_FILE_NAME}. Ignoring it.");
+                continue;
+            };
+            cmd = match value {
+                Value::Scalar(value) => cmd.mut_arg(id, |arg| arg.default_value(value)),
+                Value::Array(values) => cmd.mut_arg(id, |arg| arg.default_values(values)),
+            };
+        }
+        cmd
+    }
+}
+
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    for (i, c) in line.char_indices() {
+        match (c, quote) {
+            ('"' | '\'', None) => quote = Some(c),
+            (c, Some(q)) if c == q => quote = None,
+            ('#', None) => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+/// Removes the quotes around a string, or returns bare values like numbers as they are
+fn unquote(s: &str) -> Result<String> {
+    if let Some(literal) = s.strip_prefix('\'') {
+        return literal
+            .strip_suffix('\'')
+            .map(String::from)
+            .ok_or_else(|| anyhow!("Unterminated string"));
+    }
+    let Some(basic) = s.strip_prefix('"') else {
+        return Ok(s.to_string());
+    };
+    let basic = basic
+        .strip_suffix('"')
+        .ok_or_else(|| anyhow!("Unterminated string"))?;
+    let mut unescaped = String::new();
+    let mut chars = basic.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        unescaped.push(match chars.next() {
+            Some('n') => '\n',
+            Some('t') => '\t',
+            Some(c @ ('"' | '\\')) => c,
+            c => bail!("Unsupported escape sequence \\{}", c.unwrap_or_default()),
+        });
+    }
+    Ok(unescaped)
+}
+
+/// Splits the items of an array at the commas that are outside of strings
+fn split_items(items: &str) -> Vec<&str> {
+    let mut split = Vec::new();
+    let mut quote = None;
+    let mut start = 0;
+    for (i, c) in items.char_indices() {
+        match (c, quote) {
+            ('"' | '\'', None) => quote = Some(c),
+            (c, Some(q)) if c == q => quote = None,
+            (',', None) => {
+                split.push(&items[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    split.push(&items[start..]);
+    split
+}
+
+fn parse_value(s: &str) -> Result<Value> {
+    let Some(items) = s.strip_prefix('[') else {
+        return unquote(s).map(Value::Scalar);
+    };
+    let items = items
+        .strip_suffix(']')
+        .ok_or_else(|| anyhow!("Arrays must be written on a single line"))?;
+    split_items(items)
+        .into_iter()
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(unquote)
+        .collect::<Result<_>>()
+        .map(Value::Array)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::checker::Lang;
+    use crate::config::{Config, Value};
+    use std::path::Path;
+
+    #[test]
+    fn test_parse_config() {
+        let mut config = Config::default();
+        config
+            .merge(
+                "# Defaults for every problem\n\
+                recurse = 3\n\
+                submission-viewer = \"browser\" # Open in the browser\n\
+                template-dir = 'templates'\n\
+                ignore = [\"target\", 'build']\n",
+                Path::new("/home/user"),
+            )
+            .unwrap();
+
+        assert_eq!(config.defaults["recurse"], Value::Scalar("3".into()));
+        assert_eq!(
+            config.defaults["submission-viewer"],
+            Value::Scalar("browser".into())
+        );
+        assert_eq!(
+            config.template_dir.as_deref(),
+            Some(Path::new("/home/user/templates"))
+        );
+        assert_eq!(
+            config.defaults["ignore"],
+            Value::Array(vec!["target".into(), "build".into()])
+        );
+        assert!(config.merge("recurse 3", Path::new("/")).is_err());
+    }
+
+    #[test]
+    fn test_extra_flags() {
+        let mut config = Config::default();
+        config
+            .merge(
+                "recurse = 3\n\
+                [cpp]\n\
+                extra_flags = [\"-Wall\", \"-fsanitize=address,undefined\"]\n\
+                [rust]\n\
+                extra_flags = [\"-Ctarget-cpu=native\"]\n",
+                Path::new("/"),
+            )
+            .unwrap();
+
+        assert_eq!(
+            config.extra_flags(&Lang::Cpp),
+            ["-Wall", "-fsanitize=address,undefined"]
+        );
+        assert_eq!(config.extra_flags(&Lang::Rust), ["-Ctarget-cpu=native"]);
+        assert!(config.extra_flags(&Lang::C).is_empty());
+        assert!(config
+            .merge("[c]\nextra_flags = \"-Wall\"", Path::new("/"))
+            .is_err());
+    }
+}
@@ -1,23 +1,168 @@
-use anyhow::{anyhow, bail, Result};
+use crate::{host, interrupt, progress, proxy};
+use anyhow::{anyhow, Context, Result};
 use std::collections::HashMap;
 
-use futures::io::SeekFrom;
 use itertools::Itertools;
-use log::info;
+use log::{info, warn};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
 use std::convert::Into;
-use std::env::temp_dir;
 use std::ffi::OsStr;
 use std::fs;
-use std::io::Read;
-use std::path::Path;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, LazyLock, OnceLock};
+use std::time::{Duration, SystemTime};
 use tempfile::TempPath;
-use tokio::fs::{File, OpenOptions};
-use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt, ErrorKind};
+use tokio::io::ErrorKind;
+
+/// HTTP client shared by all requests to Kattis, so connections are reused between them
+fn client() -> Result<reqwest::Client> {
+    static CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+    if let Some(client) = CLIENT.get() {
+        return Ok(client.clone());
+    }
+    let mut builder = reqwest::Client::builder().user_agent("kattis-cli");
+    if let Some(proxy) = proxy()? {
+        builder = builder.proxy(proxy);
+    }
+    let client = builder.build()?;
+    Ok(CLIENT.get_or_init(|| client).clone())
+}
+
+/// Directory for downloaded samples and compiled binaries. It is placed in `KATTIS_TMPDIR` if
+/// set, for systems where the system temp directory is small or doesn't allow running binaries.
+pub fn kattis_dir() -> PathBuf {
+    std::env::var_os("KATTIS_TMPDIR")
+        .filter(|dir| !dir.is_empty())
+        .map_or_else(std::env::temp_dir, PathBuf::from)
+        .join("kattis")
+}
+
+/// A temporary file in the kattis directory that is removed once dropped
+pub fn temp_file() -> Result<tempfile::NamedTempFile> {
+    let file = tempfile::NamedTempFile::new_in(kattis_dir())?;
+    interrupt::remove_temp_file_on_interrupt(file.path());
+    Ok(file)
+}
 
 pub fn initialize_temp_dir() -> Result<()> {
-    let mut tmp_dir = temp_dir();
-    tmp_dir.push("kattis/problem_files/");
-    fs::create_dir_all(tmp_dir).map_err(Into::into)
+    let tmp_dir = kattis_dir();
+    fs::create_dir_all(tmp_dir.join("problem_files"))?;
+    fs::create_dir_all(tmp_dir.join("bincache")).map_err(Into::into)
+}
+
+/// Removes the downloaded samples and compiled binaries, returning the number of files and bytes freed
+pub fn clear_cache() -> Result<(usize, u64)> {
+    let cache_dir = kattis_dir();
+
+    let (mut files, mut bytes) = (0, 0);
+    for entry in walkdir::WalkDir::new(&cache_dir) {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e)
+                if e.io_error()
+                    .is_some_and(|e| e.kind() == ErrorKind::NotFound) =>
+            {
+                break
+            }
+            Err(e) => return Err(e.into()),
+        };
+        if entry.file_type().is_file() {
+            files += 1;
+            bytes += entry.metadata()?.len();
+        }
+    }
+
+    match fs::remove_dir_all(&cache_dir) {
+        Err(e) if e.kind() != ErrorKind::NotFound => return Err(e.into()),
+        _ => {}
+    }
+    initialize_temp_dir()?;
+    Ok((files, bytes))
+}
+
+/// Sample files of a problem in the cache
+pub struct CachedProblem {
+    pub name: String,
+    pub bytes: u64,
+    /// Time since the samples were downloaded
+    pub age: Duration,
+}
+
+/// The problems with cached samples for the current Kattis instance, sorted by name
+pub fn cached_problems() -> Result<Vec<CachedProblem>> {
+    let mut problems = Vec::new();
+    for entry in walkdir::WalkDir::new(problem_files_dir()).max_depth(1) {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e)
+                if e.io_error()
+                    .is_some_and(|e| e.kind() == ErrorKind::NotFound) =>
+            {
+                break
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let path = entry.path();
+        if !entry.file_type().is_file() || path.extension() != Some(OsStr::new("zip")) {
+            continue;
+        }
+        let metadata = entry.metadata()?;
+        problems.push(CachedProblem {
+            name: path.file_stem().unwrap().to_string_lossy().to_string(),
+            bytes: metadata.len(),
+            age: metadata.modified()?.elapsed().unwrap_or_default(),
+        });
+    }
+    problems.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(problems)
+}
+
+/// Formats a byte count with a binary unit, e.g. `1.5 MiB`
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+    #[allow(clippy::cast_precision_loss)]
+    let mut size = bytes as f64 / 1024.;
+    let mut unit = UNITS[0];
+    for next_unit in &UNITS[1..] {
+        if size < 1024. {
+            break;
+        }
+        size /= 1024.;
+        unit = next_unit;
+    }
+    format!("{size:.1} {unit}")
+}
+
+/// Formats a duration in its largest whole unit, e.g. `3 days`
+pub fn format_age(age: Duration) -> String {
+    const UNITS: [(&str, u64); 4] = [
+        ("day", 86_400),
+        ("hour", 3_600),
+        ("minute", 60),
+        ("second", 1),
+    ];
+    let seconds = age.as_secs();
+    let (unit, length) = UNITS
+        .into_iter()
+        .find(|(_, length)| seconds >= *length)
+        .unwrap_or(UNITS[3]);
+    let count = seconds / length;
+    format!("{count} {unit}{}", if count == 1 { "" } else { "s" })
+}
+
+/// Directory the sample zips are cached in, kept apart per Kattis instance
+fn problem_files_dir() -> PathBuf {
+    let mut dir = kattis_dir();
+    dir.push("problem_files");
+    if host() != "open.kattis.com" {
+        dir.push(host());
+    }
+    dir
 }
 
 #[derive(Debug)]
@@ -36,10 +181,19 @@ impl ProblemIO {
                 output,
             })
         } else {
-            Err(anyhow!("Kattis zip missing input or output"))
+            Err(anyhow!(
+                "Test case {name} is missing an input or output file"
+            ))
         }
     }
 
+    pub fn get_input_string(&self) -> Result<String> {
+        let mut res = String::new();
+        let mut input_file = fs::File::open(&self.input)?;
+        input_file.read_to_string(&mut res)?;
+        Ok(res)
+    }
+
     pub fn get_output_string(&self) -> Result<String> {
         let mut res = String::new();
         let mut output_file = fs::File::open(&self.output)?;
@@ -48,97 +202,249 @@ impl ProblemIO {
     }
 }
 
-fn remove_suffix(s: &str, p: Vec<&str>) -> String {
-    for pat in p {
-        if let Some(stripped) = s.strip_suffix(pat) {
-            return stripped.into();
+/// Fetches the samples for a problem, reusing the cached zip unless it is older than `max_age`
+pub async fn problem(problem_name: &str, max_age: Option<Duration>) -> Result<Vec<ProblemIO>> {
+    info!("Fetching problem {problem_name}");
+    // Fetch from Kattis
+    let mut problem_path = problem_files_dir();
+    problem_path.push(format!("{problem_name}.zip"));
+
+    let requested = SystemTime::now();
+    // Several sources for the same problem are checked concurrently, so only let one of them
+    // download the samples while the others wait for the cached copy
+    let lock = {
+        static LOCKS: LazyLock<std::sync::Mutex<HashMap<PathBuf, Arc<tokio::sync::Mutex<()>>>>> =
+            LazyLock::new(Default::default);
+        LOCKS
+            .lock()
+            .unwrap()
+            .entry(problem_path.clone())
+            .or_default()
+            .clone()
+    };
+    let _guard = lock.lock().await;
+
+    if let Some(max_age) = max_age {
+        let modified = tokio::fs::metadata(&problem_path)
+            .await
+            .and_then(|metadata| metadata.modified())
+            .ok();
+        // Samples downloaded by another task while waiting for the lock are fresh enough
+        let stale = modified.is_some_and(|modified| {
+            modified < requested && modified.elapsed().is_ok_and(|age| age >= max_age)
+        });
+        if stale {
+            log::warn!("Refreshing cached samples for {problem_name}");
+            tokio::fs::remove_file(&problem_path).await?;
         }
     }
-    s.into()
+
+    let valid_zip = |contents: &[u8]| zip::ZipArchive::new(std::io::Cursor::new(contents)).is_ok();
+    let cached = match tokio::fs::read(&problem_path).await {
+        Ok(contents) if valid_zip(&contents) => Some(contents),
+        Ok(_) => {
+            warn!("The cached samples for {problem_name} are corrupt, downloading them again");
+            tokio::fs::remove_file(&problem_path).await?;
+            None
+        }
+        Err(e) if e.kind() == ErrorKind::NotFound => None,
+        Err(e) => return Err(e.into()),
+    };
+    let contents = match cached {
+        Some(contents) => contents,
+        None => match download_samples(problem_name, &problem_path).await? {
+            Some(contents) => contents,
+            None => return Ok(Vec::new()),
+        },
+    };
+
+    let mut zip = zip::ZipArchive::new(std::io::Cursor::new(contents))?;
+    let mut file_names: Vec<_> = zip.file_names().map(String::from).collect();
+    file_names.sort();
+
+    let mut files = Vec::new();
+    for entry_name in file_names {
+        let mut zipped_file_reader = zip.by_name(&entry_name)?;
+        // Some zips keep the samples in a folder, so only the file name is used
+        let Some(file_name) = Path::new(&entry_name).file_name() else {
+            continue;
+        };
+        if zipped_file_reader.is_dir() || !is_io_file(Path::new(file_name)) {
+            info!("Ignoring {entry_name} in the samples of {problem_name}");
+            continue;
+        }
+        let file_name = file_name.to_string_lossy().to_string();
+        let mut out_file = temp_file()?;
+        std::io::copy(&mut zipped_file_reader, &mut out_file)?;
+        files.push((file_name, out_file.into_temp_path()));
+    }
+
+    info!("Problem {problem_name} fetched");
+    Ok(pair_io_files(files))
 }
 
-pub async fn problem(problem_name: &str) -> Result<Vec<ProblemIO>> {
-    info!("Fetching problem {}", problem_name);
-    // Fetch from Kattis
-    let mut problem_path = temp_dir();
-    problem_path.push(format!("kattis/problem_files/{problem_name}.zip"));
-
-    let mut problem_file = match File::open(&problem_path).await {
-        Ok(f) => f,
-        Err(e) => match e.kind() {
-            ErrorKind::NotFound => {
-                log::warn!("Downloading problem files for {problem_name} from open.kattis.com...");
-                let mut file = OpenOptions::new()
-                    .write(true)
-                    .read(true)
-                    .create(true)
-                    .open(&problem_path)
-                    .await?;
-
-                let tmp = reqwest::get(format!(
-                    "https://open.kattis.com/problems/{problem_name}/file/statement/samples.zip"
-                ))
+/// Downloads a file, retrying with exponential backoff when the connection fails or times out
+async fn download(url: &str) -> Result<Vec<u8>> {
+    const ATTEMPTS: u32 = 3;
+    let client = client()?;
+    let mut backoff = Duration::from_millis(500);
+    for attempt in 1.. {
+        let res = async {
+            let bytes = client
+                .get(url)
+                .send()
                 .await?
+                .error_for_status()?
                 .bytes()
                 .await?;
+            Ok::<_, reqwest::Error>(bytes.to_vec())
+        }
+        .await;
+        match res {
+            Err(e) if (e.is_connect() || e.is_timeout()) && attempt < ATTEMPTS => {
+                log::warn!(
+                    "Download failed: {e}\nRetrying in {:.1}s (attempt {}/{ATTEMPTS})...",
+                    backoff.as_secs_f64(),
+                    attempt + 1
+                );
+                tokio::time::sleep(backoff).await;
+                backoff *= 2;
+            }
+            res => return res.map_err(Into::into),
+        }
+    }
+    unreachable!()
+}
 
-                file.write_all(&tmp).await?;
-                file.seek(SeekFrom::Start(0)).await?;
-                // file.start_seek(SeekFrom::Start(0)).await?;
-                file
+/// Downloads the samples and moves them into the cache once they are known to be a valid zip, so
+/// an interrupted download never leaves a broken cache behind. Returns `None` if the problem has
+/// no samples.
+async fn download_samples(problem_name: &str, problem_path: &Path) -> Result<Option<Vec<u8>>> {
+    log::warn!(
+        "Downloading problem files for {problem_name} from {}...",
+        host()
+    );
+    let progress = progress::start(format!("Downloading samples for {problem_name}…"));
+    let url = format!(
+        "https://{}/problems/{problem_name}/file/statement/samples.zip",
+        host()
+    );
+    let contents = match download(&url).await {
+        Ok(contents) => contents,
+        Err(e) => {
+            let not_found = e
+                .downcast_ref::<reqwest::Error>()
+                .and_then(reqwest::Error::status)
+                == Some(reqwest::StatusCode::NOT_FOUND);
+            if not_found {
+                info!("Problem {problem_name} has no samples to download");
+                return Ok(None);
             }
-            _ => return Err(e.into()),
-        },
+            return Err(e);
+        }
     };
+    drop(progress);
 
-    let mut file_contents = Vec::with_capacity(problem_file.metadata().await?.len().try_into()?);
-    problem_file.read_buf(&mut file_contents).await?;
-    let cursor = std::io::Cursor::new(file_contents);
+    zip::ZipArchive::new(std::io::Cursor::new(contents.as_slice())).with_context(|| {
+        format!("The samples downloaded for {problem_name} are not a valid zip file")
+    })?;
+    let cache_dir = problem_path.parent().unwrap();
+    fs::create_dir_all(cache_dir)?;
+    let mut partial = tempfile::NamedTempFile::new_in(cache_dir)?;
+    interrupt::remove_temp_file_on_interrupt(partial.path());
+    partial.write_all(&contents)?;
+    partial.persist(problem_path)?;
+    Ok(Some(contents))
+}
 
-    let mut zip = zip::ZipArchive::new(cursor)?;
-    let mut file_names: Vec<_> = zip.file_names().map(String::from).collect();
-    file_names.sort();
+/// Reads the `.in`/`.ans` pairs in a directory, such as test cases written by the user
+pub fn local_problem(test_dir: &Path) -> Result<Vec<ProblemIO>> {
+    info!("Reading test cases from {}", test_dir.display());
+    let mut files = Vec::new();
+    for entry in fs::read_dir(test_dir)? {
+        let path = entry?.path();
+        if !path.is_file() || !is_io_file(&path) {
+            continue;
+        }
+        let file_name = path.file_name().unwrap().to_string_lossy().to_string();
+        files.push((file_name, copy_to_temp(&path)?));
+    }
+    Ok(pair_io_files(files))
+}
+
+/// A test case made from the given input and answer files, named after the input
+pub fn single_case(input: &Path, answer: &Path) -> Result<ProblemIO> {
+    let name = input
+        .file_stem()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string();
+    ProblemIO::new(
+        name,
+        (Some(copy_to_temp(input)?), Some(copy_to_temp(answer)?)),
+    )
+}
+
+/// Copies a file the user owns, since the `TempPath` removes its file when dropped
+fn copy_to_temp(path: &Path) -> Result<TempPath> {
+    let mut out_file = temp_file()?;
+    std::io::copy(
+        &mut fs::File::open(path).with_context(|| format!("Failed to open {}", path.display()))?,
+        &mut out_file,
+    )?;
+    Ok(out_file.into_temp_path())
+}
+
+fn has_extension(path: &Path, extension: &str) -> bool {
+    path.extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case(extension))
+}
+
+/// Whether the file is the input or answer of a test case, ignoring the case of the extension
+fn is_io_file(path: &Path) -> bool {
+    has_extension(path, "in") || has_extension(path, "ans")
+}
 
+/// Pairs up `.in` and `.ans` files with the same name into `ProblemIO`s sorted by name. Other
+/// files are ignored.
+fn pair_io_files(files: Vec<(String, TempPath)>) -> Vec<ProblemIO> {
     let mut io_map = HashMap::new();
 
-    for file_name in file_names {
-        let mut out_file = tempfile::NamedTempFile::new()?;
-        let mut zipped_file_reader = zip.by_name(&file_name)?;
-        std::io::copy(&mut zipped_file_reader, &mut out_file)?;
-        let file_path = out_file.into_temp_path();
+    for (file_name, file_path) in files {
+        let path = Path::new(&file_name);
+        let Some(name) = path.file_stem().filter(|_| is_io_file(path)) else {
+            info!("Ignoring {file_name}, which is not an .in or .ans file");
+            continue;
+        };
         let (ref mut i, ref mut o) = *io_map
-            .entry(remove_suffix(&file_name, vec![".in", ".ans"]))
+            .entry(name.to_string_lossy().to_string())
             .or_insert((None, None));
-
-        let filename_path = Path::new(&file_name);
-        let extension = filename_path.extension();
-        if extension.map_or(false, |e| e.eq_ignore_ascii_case("in")) {
+        if has_extension(path, "in") {
             *i = Some(file_path);
-        } else if extension.map_or(false, |e| e.eq_ignore_ascii_case("ans")) {
-            *o = Some(file_path);
         } else {
-            bail!("Incompatible input format");
+            *o = Some(file_path);
         }
     }
 
-    info!("Problem {problem_name} fetched");
+    // Skip incomplete cases, such as the inputs of interactive problems
     io_map
         .into_iter()
-        .map(|(name, io)| ProblemIO::new(name, io))
-        .sorted_by(|a, b| {
-            Ord::cmp(
-                a.as_ref().map(|x| x.name.as_str()).unwrap_or(""),
-                b.as_ref().map(|x| x.name.as_str()).unwrap_or(""),
-            )
+        .filter_map(|(name, io)| {
+            ProblemIO::new(name, io)
+                .map_err(|e| log::warn!("{e}. Skipping it."))
+                .ok()
         })
+        .sorted_by(|a, b| Ord::cmp(&a.name, &b.name))
         .collect()
 }
 
 pub async fn problem_exists(problem_name: &str) -> Result<bool> {
     use walkdir::DirEntry;
-    let mut problem_path = temp_dir();
-    problem_path.push("kattis/problem_files/");
-    info!("Checking if problem exists locally at {problem_path:?}");
+    let problem_path = problem_files_dir();
+    info!(
+        "Checking if problem exists locally at {}",
+        problem_path.display()
+    );
 
     let found_locally = walkdir::WalkDir::new(problem_path)
         .max_depth(1)
@@ -148,19 +454,99 @@ pub async fn problem_exists(problem_name: &str) -> Result<bool> {
             // Strip the .zip off
             let pb: Option<&Path> = f.as_ref().ok().map(DirEntry::path);
             let s = pb.and_then(Path::file_stem).map(OsStr::to_string_lossy);
-            s.map_or(false, |cow| cow == problem_name)
+            s.is_some_and(|cow| cow == problem_name)
         });
 
     if found_locally {
         return Ok(true);
     }
 
-    let str = reqwest::get(&format!("https://open.kattis.com/problems/{problem_name}"))
-        .await?
-        .text()
+    let response = client()?
+        .get(format!("https://{}/problems/{problem_name}", host()))
+        .send()
         .await?;
+    let status = response.status();
+    info!("Result of problem_exists: {status}");
+
+    if status == reqwest::StatusCode::NOT_FOUND {
+        return Ok(false);
+    }
+    let page = response.error_for_status()?.text().await?;
+    if let Some(time_limit) = parse_time_limit(&page) {
+        let meta = ProblemMeta {
+            time_limit: Some(time_limit),
+        };
+        let meta = serde_json::to_string(&meta)?;
+        // The directory of another host is only created once its samples are downloaded
+        let cached = fs::create_dir_all(problem_files_dir())
+            .and_then(|()| fs::write(meta_path(problem_name), meta));
+        if let Err(e) = cached {
+            warn!("Failed to cache the time limit of {problem_name}: {e}");
+        }
+    }
+    Ok(true)
+}
+
+/// Details from the problem page, cached next to the samples
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct ProblemMeta {
+    /// CPU time limit in seconds
+    time_limit: Option<f64>,
+}
 
-    info!("Result of problem_exists: {str}");
+fn meta_path(problem_name: &str) -> PathBuf {
+    problem_files_dir().join(format!("{problem_name}.meta.json"))
+}
+
+/// Reads the "CPU Time limit" from a problem page
+fn parse_time_limit(page: &str) -> Option<f64> {
+    static RE: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r"(?s)CPU Time limit.*?([0-9]+(?:\.[0-9]+)?)\s*seconds?").unwrap()
+    });
+    RE.captures(page)?[1].parse().ok()
+}
 
-    Ok(!str.contains("404: Not Found"))
+/// The time limit of the problem, if it was cached when the problem was first looked up
+pub fn time_limit(problem_name: &str) -> Option<Duration> {
+    let meta = fs::read_to_string(meta_path(problem_name)).ok()?;
+    let meta: ProblemMeta = serde_json::from_str(&meta).ok()?;
+    Duration::try_from_secs_f64(meta.time_limit?).ok()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::fetch::{format_age, pair_io_files, parse_time_limit};
+    use std::time::Duration;
+
+    #[test]
+    fn test_format_age() {
+        assert_eq!(format_age(Duration::from_millis(500)), "0 seconds");
+        assert_eq!(format_age(Duration::from_secs(61)), "1 minute");
+        assert_eq!(format_age(Duration::from_secs(3 * 86_400 + 5)), "3 days");
+    }
+
+    #[test]
+    fn test_parse_time_limit() {
+        let page = r#"<div class="metadata_list-item">
+            <span class="metadata_list-item-label">CPU Time limit</span>
+            <span>2.5 seconds</span></div>"#;
+        assert_eq!(parse_time_limit(page), Some(2.5));
+        assert_eq!(parse_time_limit("CPU Time limit: 1 second"), Some(1.0));
+        assert_eq!(parse_time_limit("Memory limit 1024 MB"), None);
+    }
+
+    #[test]
+    fn test_pair_io_files() {
+        let files = ["1.in", "1.ans", "2.IN", "2.Ans", "3.in", "README.md"].map(|name| {
+            (
+                name.to_string(),
+                tempfile::NamedTempFile::new().unwrap().into_temp_path(),
+            )
+        });
+        let names: Vec<String> = pair_io_files(files.into())
+            .into_iter()
+            .map(|io| io.name)
+            .collect();
+        assert_eq!(names, ["1", "2"]);
+    }
 }
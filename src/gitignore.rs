@@ -0,0 +1,173 @@
+use log::info;
+use regex::Regex;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// A line of a `.gitignore` file
+struct Pattern {
+    regex: Regex,
+    negated: bool,
+    dir_only: bool,
+    /// Patterns without a slash match the file name at any depth, the others the path relative to
+    /// the `.gitignore`
+    anchored: bool,
+}
+
+impl Pattern {
+    fn parse(line: &str) -> Option<Self> {
+        let line = line.trim_end();
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+        let (negated, line) = line
+            .strip_prefix('!')
+            .map_or((false, line), |line| (true, line));
+        let (dir_only, line) = line
+            .strip_suffix('/')
+            .map_or((false, line), |line| (true, line));
+        let anchored = line.contains('/');
+        let glob = line.strip_prefix('/').unwrap_or(line);
+        Some(Self {
+            regex: Regex::new(&glob_to_regex(glob)).ok()?,
+            negated,
+            dir_only,
+            anchored,
+        })
+    }
+
+    fn matches(&self, relative: &str, is_dir: bool) -> bool {
+        if self.dir_only && !is_dir {
+            return false;
+        }
+        if self.anchored {
+            self.regex.is_match(relative)
+        } else {
+            let file_name = relative.rsplit('/').next().unwrap_or(relative);
+            self.regex.is_match(file_name)
+        }
+    }
+}
+
+fn glob_to_regex(glob: &str) -> String {
+    let mut regex = String::from("^");
+    let mut chars = glob.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                if chars.next_if_eq(&'/').is_some() {
+                    regex.push_str("(.*/)?");
+                } else {
+                    regex.push_str(".*");
+                }
+            }
+            '*' => regex.push_str("[^/]*"),
+            '?' => regex.push_str("[^/]"),
+            '[' => {
+                let class: String = chars.by_ref().take_while(|&c| c != ']').collect();
+                let (negated, class) = class
+                    .strip_prefix('!')
+                    .map_or((false, class.as_str()), |class| (true, class));
+                regex.push('[');
+                if negated {
+                    regex.push('^');
+                }
+                regex.push_str(&class.replace('\\', "\\\\"));
+                regex.push(']');
+            }
+            '\\' => {
+                if let Some(escaped) = chars.next() {
+                    regex.push_str(&regex::escape(&escaped.to_string()));
+                }
+            }
+            c => regex.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    regex.push('$');
+    regex
+}
+
+/// The patterns of one directory's `.gitignore`
+struct Rules {
+    patterns: Vec<Pattern>,
+    /// Whether the directory is the root of a git repository, where the search for more
+    /// `.gitignore` files stops
+    repo_root: bool,
+}
+
+/// Answers whether paths are ignored by the `.gitignore` files in their directory and its parents
+/// up to the repository root. Only the common subset of the pattern syntax is supported.
+#[derive(Default)]
+pub struct Gitignore {
+    rules: HashMap<PathBuf, Rules>,
+}
+
+impl Gitignore {
+    fn rules(&mut self, dir: &Path) -> &Rules {
+        self.rules.entry(dir.to_path_buf()).or_insert_with(|| {
+            let path = dir.join(".gitignore");
+            let patterns = std::fs::read_to_string(&path).map_or_else(
+                |_| Vec::new(),
+                |contents| {
+                    info!("Reading ignore rules from {}", path.display());
+                    contents.lines().filter_map(Pattern::parse).collect()
+                },
+            );
+            Rules {
+                patterns,
+                repo_root: dir.join(".git").exists(),
+            }
+        })
+    }
+
+    /// Whether the absolute `path` is ignored. The last matching pattern decides, and the
+    /// `.gitignore` closest to the path takes precedence.
+    pub fn is_ignored(&mut self, path: &Path, is_dir: bool) -> bool {
+        let mut dirs = Vec::new();
+        for dir in path.ancestors().skip(1) {
+            dirs.push(dir);
+            if self.rules(dir).repo_root {
+                break;
+            }
+        }
+
+        let mut ignored = false;
+        for dir in dirs.into_iter().rev() {
+            let Ok(relative) = path.strip_prefix(dir) else {
+                continue;
+            };
+            let relative = relative.to_string_lossy().replace('\\', "/");
+            for pattern in &self.rules(dir).patterns {
+                if pattern.matches(&relative, is_dir) {
+                    ignored = !pattern.negated;
+                }
+            }
+        }
+        ignored
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::gitignore::Pattern;
+
+    #[test]
+    fn test_patterns() {
+        let matches = |pattern: &str, path: &str, is_dir: bool| {
+            Pattern::parse(pattern).unwrap().matches(path, is_dir)
+        };
+        assert!(matches("target/", "target", true));
+        assert!(matches("target/", "sub/target", true));
+        assert!(!matches("target/", "target", false));
+        assert!(matches("*.py", "old/hello.py", false));
+        assert!(!matches("*.py", "hello.pyc", false));
+        assert!(matches("/build", "build", true));
+        assert!(!matches("/build", "sub/build", true));
+        assert!(matches("old/**/*.cpp", "old/a/b/c.cpp", false));
+        assert!(matches("old/**/*.cpp", "old/c.cpp", false));
+        assert!(matches("test[0-9].c", "test4.c", false));
+        assert!(!matches("test[!0-9].c", "test4.c", false));
+        assert!(Pattern::parse("!keep.py").unwrap().negated);
+        assert!(Pattern::parse("# comment").is_none());
+    }
+}
@@ -0,0 +1,121 @@
+use crate::{exit_code, progress};
+use colored::Colorize;
+use std::path::{Path, PathBuf};
+use std::process::{Output, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use tokio::process::{Child, Command};
+
+enum Resource {
+    Process(u32),
+    File(PathBuf),
+}
+
+/// What has to be cleaned up if the user presses Ctrl-C right now
+struct State {
+    resources: Vec<(usize, Resource)>,
+    /// Temporary files, which are otherwise removed when their `TempPath` is dropped
+    temp_files: Vec<PathBuf>,
+}
+
+static STATE: Mutex<State> = Mutex::new(State {
+    resources: Vec::new(),
+    temp_files: Vec::new(),
+});
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// Keeps a process or file registered for cleanup until it is dropped
+pub struct Guard(Option<usize>);
+
+impl Drop for Guard {
+    fn drop(&mut self) {
+        let Some(id) = self.0 else {
+            return;
+        };
+        STATE
+            .lock()
+            .unwrap()
+            .resources
+            .retain(|(resource_id, _)| *resource_id != id);
+    }
+}
+
+fn register(resource: Resource) -> Guard {
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    STATE.lock().unwrap().resources.push((id, resource));
+    Guard(Some(id))
+}
+
+/// Kills `child` if the user interrupts while the guard is alive
+pub fn kill_on_interrupt(child: &Child) -> Guard {
+    // A child without an id has already exited
+    child
+        .id()
+        .map_or(Guard(None), |pid| register(Resource::Process(pid)))
+}
+
+/// Removes `path` if the user interrupts while the guard is alive, for files that are only
+/// usable once they are completely written, like binaries in the cache
+pub fn remove_on_interrupt(path: &Path) -> Guard {
+    register(Resource::File(path.to_path_buf()))
+}
+
+/// Removes the temporary file at `path` if the user interrupts, unless it is gone by then
+pub fn remove_temp_file_on_interrupt(path: &Path) {
+    STATE.lock().unwrap().temp_files.push(path.to_path_buf());
+}
+
+/// Like `Command::output`, but the process is killed if the user interrupts
+pub async fn output(command: &mut Command) -> std::io::Result<Output> {
+    let child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let _running = kill_on_interrupt(&child);
+    child.wait_with_output().await
+}
+
+/// Cleans up and exits when the user presses Ctrl-C. Destructors don't run on `exit`, so the
+/// child processes and unfinished files are tracked here instead.
+pub fn listen() {
+    tokio::spawn(async {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            clean_up_and_exit();
+        }
+    });
+}
+
+fn clean_up_and_exit() -> ! {
+    let state = STATE.lock().unwrap();
+    let mut processes = 0;
+    let mut files = 0;
+    for (_, resource) in &state.resources {
+        match resource {
+            Resource::Process(pid) => {
+                // On Windows the console sends Ctrl-C to the children as well
+                #[cfg(unix)]
+                if let Ok(pid) = libc::pid_t::try_from(*pid) {
+                    // SAFETY: kill has no memory safety requirements
+                    unsafe { libc::kill(pid, libc::SIGKILL) };
+                }
+                processes += 1;
+            }
+            Resource::File(path) => files += usize::from(std::fs::remove_file(path).is_ok()),
+        }
+    }
+    for path in &state.temp_files {
+        std::fs::remove_file(path).ok();
+    }
+    drop(state);
+
+    progress::suspend(|| {
+        eprintln!(
+            "\n{} Stopped {processes} running process{} and removed {files} unfinished file{}.",
+            "Interrupted.".yellow().bold(),
+            if processes == 1 { "" } else { "es" },
+            if files == 1 { "" } else { "s" }
+        );
+    });
+    std::process::exit(exit_code::INTERRUPTED);
+}
@@ -0,0 +1,25 @@
+use crate::{host, use_color};
+use std::io::IsTerminal;
+
+pub fn display_link(url: &str) -> String {
+    display_link_with_name(url, url)
+}
+
+/// Add link if outputting to a terminal, unless colors are turned off
+pub fn display_link_with_name(url: &str, name: &str) -> String {
+    if std::io::stdout().is_terminal() && use_color() {
+        format!("\u{1b}]8;;{url}\u{1b}\\{name}\u{1b}]8;;\u{1b}\\")
+    } else {
+        name.to_string()
+    }
+}
+
+/// URL of the problem statement on Kattis
+pub fn problem_url(problem_name: &str) -> String {
+    format!("https://{}/problems/{problem_name}", host())
+}
+
+/// `name` linked to the problem statement on Kattis
+pub fn problem_link(problem_name: &str, name: &str) -> String {
+    display_link_with_name(&problem_url(problem_name), name)
+}
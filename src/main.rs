@@ -1,42 +1,145 @@
 #![warn(clippy::all, clippy::pedantic, clippy::nursery, clippy::cargo)]
 
-use crate::checker::{find_source_from_path, Problem, ProblemSource};
+use crate::checker::{
+    find_source_from_path, CheckOptions, CppCompiler, Lang, Outcome, OutputFormat, Problem,
+    ProblemReport, ProblemSource, PythonImpl,
+};
+use crate::compare::{CompareOptions, DiffStyle};
+use crate::config::Config;
 use anyhow::{Context, Result};
-use clap::builder::NonEmptyStringValueParser;
+use clap::builder::{NonEmptyStringValueParser, PossibleValuesParser, RangedU64ValueParser};
 use clap::parser::ValueSource;
-use clap::{arg, crate_version, ArgAction, Command, ValueHint};
+use clap::{arg, crate_version, Arg, ArgAction, ArgGroup, ArgMatches, Command, ValueHint};
 use colored::Colorize;
-use log::{info, warn};
-use std::path::Path;
+use enum_iterator::all;
+use itertools::Itertools;
+use log::{info, warn, LevelFilter};
+use std::fmt::Write as _;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
 use std::sync::OnceLock;
+use std::time::Duration;
 use submit::viewer;
 
 mod checker;
 mod compare;
+mod completions;
+mod config;
 mod fetch;
+mod gitignore;
+mod interrupt;
+mod link;
+mod progress;
+mod scaffold;
+mod stress;
 mod submit;
 
 pub static RECURSE_DEPTH: OnceLock<usize> = OnceLock::new();
+pub static HOST: OnceLock<String> = OnceLock::new();
+pub static PROXY: OnceLock<String> = OnceLock::new();
 
-#[allow(clippy::cognitive_complexity)]
+/// The Kattis instance to fetch problems from, e.g. `open.kattis.com`
+pub fn host() -> &'static str {
+    HOST.get().map_or("open.kattis.com", String::as_str)
+}
+
+/// Whether to print colors and hyperlinks. Off with `--no-color`, `NO_COLOR` or when piping stdout.
+pub(crate) fn use_color() -> bool {
+    colored::control::SHOULD_COLORIZE.should_colorize()
+}
+
+/// Proxy for all requests to Kattis, from `--proxy` or else the `HTTPS_PROXY`/`HTTP_PROXY`
+/// environment variables
+pub(crate) fn proxy() -> Result<Option<reqwest::Proxy>> {
+    let url = PROXY.get().cloned().or_else(|| {
+        ["HTTPS_PROXY", "https_proxy", "HTTP_PROXY", "http_proxy"]
+            .iter()
+            .find_map(|var| std::env::var(var).ok().filter(|url| !url.is_empty()))
+    });
+    url.map(|url| reqwest::Proxy::all(&url).with_context(|| format!("Invalid proxy URL {url}")))
+        .transpose()
+}
+
+/// Exit codes, ordered by severity so that the worst outcome among the problems is reported
+mod exit_code {
+    pub const PASSED: i32 = 0;
+    pub const FAILED: i32 = 1;
+    pub const COMPILE_ERROR: i32 = 2;
+    pub const RUNTIME_ERROR: i32 = 3;
+    pub const FETCH_ERROR: i32 = 4;
+    pub const OTHER_ERROR: i32 = 5;
+    /// 128 + SIGINT, like shells report programs stopped by Ctrl-C
+    pub const INTERRUPTED: i32 = 130;
+}
+
+#[allow(clippy::cognitive_complexity, clippy::too_many_lines)]
 fn build_cli() -> Command {
     Command::new("Kattis Tester")
         .version(crate_version!())
         .author("Marcel Rød")
         .about("Tests and submits Kattis competitive programming problems.")
+        .after_help(
+            "Exit codes: 0 all problems passed, 1 a test case failed, 2 a compile error, \
+            3 a runtime error, 4 the samples couldn't be fetched, 5 any other error. \
+            With several problems the highest code is used.",
+        )
         .arg(
             arg!([problems] ...)
                 .help(
                     "Paths of files to test or no arguments.\nFilenames should be of the format {problem}.{ext} \
                     where {problem} can be found from the url of the kattis problem \
-                    at open.kattis.com/problems/{problem}.\n\
+                    at open.kattis.com/problems/{problem}. The extension can be left out if there is only one source \
+                    for the problem, or --lang picks between them.\n\
                     If left empty, the problem to run will be inferred by looking for \
-                    the latest edited valid source file in the working directory.",
+                    the latest edited valid source file in the working directory. \
+                    A directory is searched the same way, up to the depth given by --recurse.",
                 )
                 .required(false)
                 .value_parser(NonEmptyStringValueParser::new())
                 .value_hint(ValueHint::FilePath)
                 .value_name("PROBLEM"))
+        .arg(
+            arg!(--host <HOST>)
+                .help("Kattis instance to fetch problems from, such as {school}.kattis.com. \
+                    Submissions use the URLs in .kattisrc.")
+                .required(false)
+                .value_parser(NonEmptyStringValueParser::new())
+                .default_value("open.kattis.com")
+                .action(ArgAction::Set))
+        .arg(
+            arg!(--proxy <URL>)
+                .help("Proxy to send all requests through, such as http://proxy.example.com:8080. \
+                    Defaults to the HTTPS_PROXY or HTTP_PROXY environment variable.")
+                .required(false)
+                .value_parser(NonEmptyStringValueParser::new())
+                .value_hint(ValueHint::Url)
+                .action(ArgAction::Set))
+        .arg(
+            arg!(--problem <SLUG>)
+                .short('p')
+                .help("Problem to test and submit against, instead of the one named by the file name. \
+                    Can only be used with a single source file.")
+                .required(false)
+                .value_parser(NonEmptyStringValueParser::new())
+                .action(ArgAction::Set))
+        .arg(
+            arg!(--lang <EXT>)
+                .short('l')
+                .help("Only use source files of this language, such as cpp or py. \
+                    Picks between sources for the same problem in different languages.")
+                .required(false)
+                .value_parser(PossibleValuesParser::new(all::<Lang>().map(|lang| lang.extension())))
+                .action(ArgAction::Set))
+        .arg(
+            arg!(--with <FILE>)
+                .help("Additional files that belong to the solution, like headers or helper sources. \
+                    They are compiled along with the source and submitted as separate files. \
+                    Can only be used with a single source file.")
+                .required(false)
+                .num_args(1..)
+                .value_parser(clap::value_parser!(PathBuf))
+                .value_hint(ValueHint::FilePath)
+                .action(ArgAction::Append))
         .arg(
             arg!(--submit)
                 .short('s')
@@ -53,55 +156,726 @@ fn build_cli() -> Command {
                 .requires("submit")
                 .action(ArgAction::SetTrue)
         )
+        .arg(
+            arg!(--"submit-only")
+                .help("Submit without fetching the samples or running the program, \
+                    e.g. to resubmit after a judge outage.")
+                .required(false)
+                .default_value("false")
+                .action(ArgAction::SetTrue)
+        )
+        .group(
+            ArgGroup::new("submitting")
+                .args(["submit", "submit-only"])
+                .multiple(true)
+        )
+        .arg(
+            arg!(--"dry-run")
+                .help("Print what would be submitted instead of submitting.")
+                .required(false)
+                .default_value("false")
+                .requires("submitting")
+                .action(ArgAction::SetTrue)
+        )
+        .arg(
+            arg!(--refresh)
+                .help("Download the sample files again instead of using the cached copies.")
+                .required(false)
+                .default_value("false")
+                .action(ArgAction::SetTrue)
+        )
+        .arg(
+            arg!(--"sample-ttl" <DAYS>)
+                .help("Download the sample files again if the cached copies are older than this many days.")
+                .required(false)
+                .value_parser(|s: &str| {
+                    s.parse::<f64>()
+                        .map_err(|e| e.to_string())
+                        .and_then(|days| Duration::try_from_secs_f64(days * 86400.).map_err(|e| e.to_string()))
+                })
+                .action(ArgAction::Set)
+        )
+        .arg(
+            arg!(--confirm)
+                .help("Ask for confirmation before submitting each problem. Only asks when attached to a terminal.")
+                .required(false)
+                .default_value("false")
+                .action(ArgAction::SetTrue)
+        )
+        .arg(
+            arg!(--yes)
+                .short('y')
+                .help("Submit without asking for confirmation, even if --confirm is set.")
+                .required(false)
+                .default_value("false")
+                .action(ArgAction::SetTrue)
+        )
+        .arg(
+            arg!(--"no-cache")
+                .help("Always recompile, instead of reusing the cached binary for unchanged source files.")
+                .required(false)
+                .default_value("false")
+                .action(ArgAction::SetTrue)
+        )
+        .arg(
+            arg!(--"keep-binary")
+                .help("Keep compiled binaries after running and print where they are stored.")
+                .required(false)
+                .default_value("false")
+                .action(ArgAction::SetTrue)
+        )
+        .arg(
+            arg!(--lint)
+                .help("Warn about source code that reads its input from a file instead of stdin.")
+                .required(false)
+                .default_value("false")
+                .action(ArgAction::SetTrue)
+        )
+        .arg(
+            arg!(--"show-warnings")
+                .help("Print compiler warnings even when compilation succeeds.")
+                .required(false)
+                .default_value("false")
+                .action(ArgAction::SetTrue)
+        )
         .arg(
             arg!(--recurse <DEPTH>)
                 .short('r')
-                .help("Number of directory levels to recurse into when searching for problem solutions.")
+                .help("Number of directory levels to recurse into when searching for problem solutions, \
+                    both in the working directory and in directories given instead of source files. \
+                    Hidden files and files ignored by .gitignore are skipped.")
                 .required(false)
                 .value_parser(|s: &str| s.parse::<usize>().or_else(|e| {
                     if s.to_lowercase() == "true" { Ok(100) } else { Err(e) }
                 }))
                 .default_value("1")
+                .global(true)
                 .action(ArgAction::Set)
         )
         .arg(
-            arg!(--"submission-viewer")
-                .help("Viewer to use for submission.")
+            arg!(--"python-impl" <IMPL>)
+                .help("Python implementation to run Python solutions with locally. Doesn't change the language they are submitted as.")
                 .required(false)
-                .default_value("cli")
-                // .requires("submit")  // Warn instead of disallowing
+                .value_parser(clap::value_parser!(PythonImpl))
+                .default_value("cpython")
                 .action(ArgAction::Set)
-                // .value_hint(ValueHint)
-                .value_parser(viewer::SubmissionViewerParser)
         )
+        .args(compiler_args())
+        .arg(
+            arg!(--timeout <SECONDS>)
+                .help("Time limit for each test case. Runs that exceed it are killed and reported as a local time limit exceeded.")
+                .required(false)
+                .value_parser(parse_seconds)
+                .default_value("10")
+                .action(ArgAction::Set)
+        )
+        .arg(
+            arg!(--"mem-limit" <MB>)
+                .help("Address space limit for each test case in megabytes. Only enforced on Unix. \
+                    Kotlin and C# programs get it as their maximum heap size instead, since their runtimes \
+                    reserve more address space than they use.")
+                .required(false)
+                .value_parser(clap::value_parser!(u64).range(1..))
+                .action(ArgAction::Set)
+        )
+        .arg(
+            arg!(--jobs <N>)
+                .short('j')
+                .help("Number of test cases to run at the same time. Defaults to the number of logical cores.")
+                .required(false)
+                .value_parser(RangedU64ValueParser::<usize>::new().range(1..))
+                .action(ArgAction::Set)
+        )
+        .arg(
+            arg!(--"fail-fast")
+                .help("Stop checking the other problems as soon as one of them fails. \
+                    Submissions that have started are still finished.")
+                .required(false)
+                .default_value("false")
+                .action(ArgAction::SetTrue)
+        )
+        .arg(
+            arg!(--stream)
+                .help("Print the output of each test case to stderr while it runs, to debug programs that get stuck. \
+                    Runs one test case at a time.")
+                .required(false)
+                .default_value("false")
+                .conflicts_with("interactive")
+                .action(ArgAction::SetTrue)
+        )
+        .arg(
+            arg!(--repeat <N>)
+                .help("Run each test case N times and report the fastest and median runtimes. \
+                    The output is only checked for the first run. Use with --jobs 1 for stable timings.")
+                .required(false)
+                .value_parser(RangedU64ValueParser::<usize>::new().range(1..))
+                .default_value("1")
+                .action(ArgAction::Set)
+        )
+        .arg(
+            arg!(--"test-dir" <PATH>)
+                .help("Directory of extra test cases ({name}.in and {name}.ans) to run in addition to the samples from Kattis.")
+                .required(false)
+                .value_parser(clap::value_parser!(PathBuf))
+                .value_hint(ValueHint::DirPath)
+                .action(ArgAction::Set)
+        )
+        .arg(
+            arg!(--input <FILE>)
+                .help("Run a single test case with this input instead of the samples. Requires --expected.")
+                .required(false)
+                .requires("expected")
+                .value_parser(clap::value_parser!(PathBuf))
+                .value_hint(ValueHint::FilePath)
+                .action(ArgAction::Set)
+        )
+        .arg(
+            arg!(--expected <FILE>)
+                .help("Expected output of the test case given with --input.")
+                .required(false)
+                .requires("input")
+                .value_parser(clap::value_parser!(PathBuf))
+                .value_hint(ValueHint::FilePath)
+                .action(ArgAction::Set)
+        )
+        .arg(
+            arg!(--case <NAME>)
+                .help("Only run the test case with this name, such as 1 or the name of a file in --test-dir. \
+                    Can be given several times.")
+                .required(false)
+                .value_parser(NonEmptyStringValueParser::new())
+                .action(ArgAction::Append)
+        )
+        .args(compare_args())
+        .arg(
+            arg!(--checker <PATH>)
+                .help("Program that judges each output instead of comparing it to the expected answer. \
+                    It is run as {checker} {input} {output} {expected output}, and accepts the output by exiting with status 0.")
+                .required(false)
+                .value_parser(clap::value_parser!(PathBuf))
+                .value_hint(ValueHint::ExecutablePath)
+                .action(ArgAction::Set)
+        )
+        .arg(
+            arg!(--interactive <JUDGE>)
+                .help("Run interactive problems against a judge program. The judge is run as {judge} {input} {expected output}, \
+                    with its stdout connected to the program's stdin and the program's stdout to its stdin. \
+                    It accepts by exiting with status 0 or 42.")
+                .required(false)
+                .conflicts_with("checker")
+                .value_parser(clap::value_parser!(PathBuf))
+                .value_hint(ValueHint::ExecutablePath)
+                .action(ArgAction::Set)
+        )
+        .subcommands(subcommands())
+        .args(output_args())
+        .args(viewer_args())
+}
+
+fn parse_seconds(s: &str) -> Result<Duration, String> {
+    s.parse::<f64>()
+        .map_err(|e| e.to_string())
+        .and_then(|secs| Duration::try_from_secs_f64(secs).map_err(|e| e.to_string()))
+}
+
+/// Flags for the compilers used to build solutions locally
+fn compiler_args() -> [Arg; 3] {
+    [
+        arg!(--compiler <COMPILER>)
+            .help("Compiler to build C++ solutions with locally, using the same flags. Kattis compiles with g++.")
+            .required(false)
+            .value_parser(clap::value_parser!(CppCompiler))
+            .default_value("g++")
+            .action(ArgAction::Set),
+        arg!(--"gcc-bin" <NAME>)
+            .help("Executable to compile C++ with instead of the one chosen by --compiler, such as g++-13 \
+                to match the version Kattis uses. Its version is logged with --verbose.")
+            .required(false)
+            .conflicts_with("compiler")
+            .value_parser(NonEmptyStringValueParser::new())
+            .value_hint(ValueHint::CommandName)
+            .action(ArgAction::Set),
+        arg!(--"rustc-bin" <NAME>)
+            .help("Executable to compile Rust with instead of rustc, such as a specific toolchain's rustc. \
+                Its version is logged with --verbose.")
+            .required(false)
+            .value_parser(NonEmptyStringValueParser::new())
+            .value_hint(ValueHint::CommandName)
+            .action(ArgAction::Set),
+    ]
+}
+
+/// Flags for how the results are printed
+fn output_args() -> [Arg; 5] {
+    [
+        arg!(--verbose)
+            .short('v')
+            .help("Log what is being fetched, compiled and run. Use -vv for more details. \
+                Ignored if RUST_LOG is set.")
+            .required(false)
+            .action(ArgAction::Count),
+        arg!(--quiet)
+            .short('q')
+            .help("Only print the problems and test cases that fail.")
+            .required(false)
+            .default_value("false")
+            .action(ArgAction::SetTrue),
+        arg!(--compact)
+            .help("Print a single line per problem. The test cases are only shown for failed problems, or with --verbose.")
+            .required(false)
+            .default_value("false")
+            .action(ArgAction::SetTrue),
+        arg!(--"no-color")
+            .help("Print without colors and hyperlinks. Also enabled by setting NO_COLOR.")
+            .required(false)
+            .default_value("false")
+            .action(ArgAction::SetTrue),
+        arg!(--output <FORMAT>)
+            .help("Format of the results written to stdout.")
+            .required(false)
+            .value_parser(clap::value_parser!(OutputFormat))
+            .default_value("human")
+            .action(ArgAction::Set),
+    ]
+}
+
+/// Flags for how the output is compared with the expected output
+fn compare_args() -> [Arg; 7] {
+    [
+        arg!(--tolerance <EPS>)
+            .help("Accept real numbers in the output within this absolute or relative error of the expected answer. \
+                By default, numbers are rounded to the precision of the expected answer.")
+            .required(false)
+            .value_parser(clap::value_parser!(f64))
+            .action(ArgAction::Set),
+        arg!(--"whitespace-insensitive")
+            .help("Compare output and answer as sequences of tokens, ignoring how they are separated by whitespace.")
+            .required(false)
+            .default_value("false")
+            .action(ArgAction::SetTrue),
+        arg!(--"strict-trailing")
+            .help("Fail when the output ends with a different number of empty lines than the answer. \
+                By default trailing empty lines are ignored, like on Kattis.")
+            .required(false)
+            .default_value("false")
+            .action(ArgAction::SetTrue),
+        arg!(--unordered)
+            .help("Accept the lines of the output in any order.")
+            .required(false)
+            .default_value("false")
+            .action(ArgAction::SetTrue),
+        arg!(--"max-diff-lines" <N>)
+            .help("Number of differing lines to show for a failed test case before truncating. Use 0 to show every line.")
+            .required(false)
+            .value_parser(clap::value_parser!(usize))
+            .default_value("50")
+            .action(ArgAction::Set),
+        arg!(--"show-input" [LINES])
+            .help("Print the input of failed test cases, truncated to LINES lines.")
+            .required(false)
+            .value_parser(clap::value_parser!(usize))
+            .num_args(0..=1)
+            .default_missing_value("20")
+            .action(ArgAction::Set),
+        arg!(--"diff-style" <STYLE>)
+            .help("How to show the differences for a failed test case.")
+            .required(false)
+            .value_parser(clap::value_parser!(DiffStyle))
+            .default_value("stacked")
+            .action(ArgAction::Set),
+    ]
+}
+
+/// Flags for following a submission after it is sent
+fn viewer_args() -> [Arg; 3] {
+    [
+        arg!(--"submission-viewer")
+            .help("Viewer to use for submission.")
+            .required(false)
+            .default_value("cli")
+            // .requires("submit")  // Warn instead of disallowing
+            .action(ArgAction::Set)
+            // .value_hint(ValueHint)
+            .value_parser(viewer::SubmissionViewerParser),
+        arg!(--"poll-interval" <MS>)
+            .help("Milliseconds to wait between checking the status of a submission in the CLI viewer.")
+            .required(false)
+            .value_parser(RangedU64ValueParser::<u64>::new().range(100..))
+            .default_value("1000")
+            .action(ArgAction::Set),
+        arg!(--"poll-timeout" <SECONDS>)
+            .help("Give up following a submission in the CLI viewer after this many seconds.")
+            .required(false)
+            .value_parser(RangedU64ValueParser::<u64>::new().range(1..))
+            .default_value("300")
+            .action(ArgAction::Set),
+    ]
+}
+
+/// The subcommands next to the default of checking problems
+fn subcommands() -> [Command; 9] {
+    [
+        Command::new("clear-cache")
+            .about("Remove the downloaded sample files and compiled binaries."),
+        Command::new("list-cache")
+            .about("List the problems with downloaded sample files, with their size and age."),
+        Command::new("new")
+            .about("Create a source file for a problem from a template.")
+            .arg(
+                arg!(<PROBLEM>)
+                    .help("Name of the problem, as found in the url open.kattis.com/problems/{problem}.")
+                    .value_parser(NonEmptyStringValueParser::new())
+            )
+            .arg(
+                arg!(--lang <EXT>)
+                    .short('l')
+                    .help("Extension of the language to use, such as cpp or py.")
+                    .required(true)
+                    .value_parser(PossibleValuesParser::new(all::<Lang>().map(|lang| lang.extension())))
+                    .action(ArgAction::Set)
+            )
+            .arg(
+                arg!(--fetch)
+                    .help("Download the sample files for the problem right away.")
+                    .required(false)
+                    .default_value("false")
+                    .action(ArgAction::SetTrue)
+            ),
+        Command::new("history")
+            .about("Show the latest submissions made with this tool.")
+            .arg(
+                arg!(-n --count <N>)
+                    .help("Number of submissions to show.")
+                    .required(false)
+                    .value_parser(clap::value_parser!(usize))
+                    .default_value("10")
+                    .action(ArgAction::Set)
+            ),
+        Command::new("languages")
+            .about("List the supported languages and how they are run."),
+        Command::new("test-all")
+            .about("Check every solution under a directory that is named after a problem, for example after changing a shared template. \
+                Flags for checking, like --timeout, go before test-all, except for --recurse.")
+            .arg(
+                arg!(<DIR>)
+                    .help("Directory with the solutions. Use --recurse to include its subdirectories.")
+                    .value_parser(clap::value_parser!(PathBuf))
+                    .value_hint(ValueHint::DirPath)
+            ),
+        Command::new("open")
+            .about("Open the problem statement in the browser.")
+            .arg(
+                arg!([PROBLEM])
+                    .help("Name of the problem. Defaults to the problem of the most recently modified source file.")
+                    .value_parser(NonEmptyStringValueParser::new())
+            ),
+        stress_command(),
+        Command::new("completions")
+            .about("Print a shell completion script.")
+            .arg(
+                arg!(<SHELL>)
+                    .help("Shell to generate completions for.")
+                    .value_parser(PossibleValuesParser::new(completions::SHELLS))
+            ),
+    ]
+}
+
+/// The stress subcommand, which has too many arguments to list inline
+fn stress_command() -> Command {
+    Command::new("stress")
+        .about("Compare a solution with a brute force solution on generated inputs until they disagree.")
+        .arg(
+            arg!(<SOLUTION>)
+                .help("Source file of the solution to test.")
+                .value_parser(clap::value_parser!(PathBuf))
+                .value_hint(ValueHint::FilePath)
+        )
+        .arg(
+            arg!(--brute <SOURCE>)
+                .help("Source file of a slow but correct solution, whose output is used as the answer.")
+                .required(true)
+                .value_parser(clap::value_parser!(PathBuf))
+                .value_hint(ValueHint::FilePath)
+                .action(ArgAction::Set)
+        )
+        .arg(
+            arg!(--generator <COMMAND>)
+                .help("Command that prints a random input, such as \"python3 gen.py\". \
+                    The test number is passed as its last argument to use as a seed.")
+                .required(true)
+                .value_parser(NonEmptyStringValueParser::new())
+                .action(ArgAction::Set)
+        )
+        .arg(
+            arg!(--iterations <N>)
+                .help("Number of inputs to generate before giving up on finding a difference.")
+                .required(false)
+                .value_parser(RangedU64ValueParser::<usize>::new().range(1..))
+                .default_value("100")
+                .action(ArgAction::Set)
+        )
+        .arg(
+            arg!(--timeout <SECONDS>)
+                .help("Time limit for each run of the generator and the solutions.")
+                .required(false)
+                .value_parser(parse_seconds)
+                .default_value("10")
+                .action(ArgAction::Set)
+        )
+        .args(compare_args())
+}
+
+/// Reads the flags from `compare_args`
+fn compare_options(matches: &ArgMatches) -> CompareOptions {
+    CompareOptions {
+        tolerance: matches.get_one("tolerance").copied(),
+        whitespace_insensitive: matches
+            .get_one("whitespace-insensitive")
+            .copied()
+            .unwrap_or(false),
+        unordered: matches.get_one("unordered").copied().unwrap_or(false),
+        strict_trailing: matches.get_one("strict-trailing").copied().unwrap_or(false),
+        max_diff_lines: matches.get_one("max-diff-lines").copied().unwrap(),
+        diff_style: matches.get_one("diff-style").copied().unwrap(),
+    }
+}
+
+/// Reads the settings that apply to checking every problem
+fn check_options(matches: &ArgMatches) -> CheckOptions {
+    let mem_limit: Option<u64> = matches.get_one("mem-limit").copied();
+    if cfg!(not(unix)) && mem_limit.is_some() {
+        warn!(
+            "{} is only supported on Unix. Ignoring it.",
+            "--mem-limit".bold()
+        );
+    }
+    let stream = matches.get_one("stream").copied().unwrap_or(false);
+
+    CheckOptions {
+        force: matches.get_one("force").copied().unwrap_or(false),
+        submission_viewer: matches.get_one("submission-viewer").copied().unwrap(),
+        dry_run: matches.get_one("dry-run").copied().unwrap_or(false),
+        submit_only: matches.get_one("submit-only").copied().unwrap_or(false),
+        lint: matches.get_one("lint").copied().unwrap_or(false),
+        poll: viewer::PollOptions {
+            interval: Duration::from_millis(matches.get_one("poll-interval").copied().unwrap()),
+            timeout: Duration::from_secs(matches.get_one("poll-timeout").copied().unwrap()),
+        },
+        timeout: matches.get_one("timeout").copied().unwrap(),
+        mem_limit: mem_limit.filter(|_| cfg!(unix)),
+        test_dir: matches.get_one("test-dir").cloned(),
+        single_case: matches
+            .get_one::<PathBuf>("input")
+            .cloned()
+            .zip(matches.get_one::<PathBuf>("expected").cloned()),
+        cases: matches
+            .get_many("case")
+            .unwrap_or_default()
+            .cloned()
+            .collect(),
+        compare: compare_options(matches),
+        checker: matches.get_one("checker").cloned(),
+        interactive: matches.get_one("interactive").cloned(),
+        output: matches.get_one("output").copied().unwrap(),
+        quiet: matches.get_one("quiet").copied().unwrap_or(false),
+        show_input: matches.get_one("show-input").copied(),
+        fail_fast: matches.get_one("fail-fast").copied().unwrap_or(false),
+        compact: matches.get_one("compact").copied().unwrap_or(false)
+            && matches.get_count("verbose") == 0,
+        confirm: matches.get_one("confirm").copied().unwrap_or(false)
+            && !matches.get_one("yes").copied().unwrap_or(false)
+            && std::io::stdin().is_terminal(),
+        repeat: matches.get_one("repeat").copied().unwrap(),
+        stream,
+        // Streamed output of cases running at the same time would be interleaved
+        jobs: if stream {
+            1
+        } else {
+            matches.get_one("jobs").copied().unwrap_or_else(|| {
+                std::thread::available_parallelism().map_or(1, std::num::NonZeroUsize::get)
+            })
+        },
+        sample_max_age: if matches.get_one("refresh").copied().unwrap_or(false) {
+            Some(Duration::ZERO)
+        } else {
+            matches.get_one("sample-ttl").copied()
+        },
+    }
+}
+
+/// Prints a tally of the problem outcomes, like "3/4 problems passed, 1 compile error"
+fn print_summary(
+    total: usize,
+    passed: usize,
+    failed: usize,
+    compile_errors: usize,
+    errors: usize,
+    cancelled: usize,
+    no_samples: usize,
+) {
+    let tally = format!("{passed}/{total}");
+    let tally = if passed == total {
+        tally.green()
+    } else {
+        tally.red()
+    };
+    let mut summary = format!("{} problems passed", tally.bold());
+    for (count, singular, plural) in [
+        (failed, "failed", "failed"),
+        (compile_errors, "compile error", "compile errors"),
+        (errors, "error", "errors"),
+        (cancelled, "cancelled", "cancelled"),
+        (no_samples, "without samples", "without samples"),
+    ] {
+        if count > 0 {
+            let label = if count == 1 { singular } else { plural };
+            write!(summary, ", {} {label}", count.to_string().red().bold()).unwrap();
+        }
+    }
+    println!("{summary}");
+}
+
+/// Runs the subcommand with the given name and returns the exit code
+async fn run_subcommand(name: &str, sub_matches: &ArgMatches, config: &Config) -> i32 {
+    match name {
+        "completions" => {
+            let shell: &String = sub_matches.get_one("SHELL").unwrap();
+            print!("{}", completions::generate(shell, build_cli()));
+            0
+        }
+        "new" => {
+            let problem_name: &String = sub_matches.get_one("PROBLEM").unwrap();
+            let lang =
+                Lang::from_extension(sub_matches.get_one::<String>("lang").unwrap()).unwrap();
+            let fetch_samples = sub_matches.get_one("fetch").copied().unwrap_or(false);
+            let template_dir = config
+                .template_dir
+                .clone()
+                .or_else(|| dirs::config_dir().map(|dir| dir.join("kattis-rs/templates")));
+            match scaffold::new_problem(problem_name, &lang, template_dir.as_deref(), fetch_samples)
+                .await
+            {
+                Ok(_) => 0,
+                Err(e) => {
+                    eprintln!("Failed to create problem {problem_name}: {e}");
+                    1
+                }
+            }
+        }
+        "history" => {
+            let count: usize = sub_matches.get_one("count").copied().unwrap();
+            match submit::history::print(count) {
+                Ok(()) => 0,
+                Err(e) => {
+                    eprintln!("Failed to read the submission history: {e}");
+                    1
+                }
+            }
+        }
+        "stress" => {
+            let solution: &PathBuf = sub_matches.get_one("SOLUTION").unwrap();
+            let brute: &PathBuf = sub_matches.get_one("brute").unwrap();
+            let options = stress::StressOptions {
+                generator: sub_matches.get_one::<String>("generator").unwrap().clone(),
+                iterations: sub_matches.get_one("iterations").copied().unwrap(),
+                timeout: sub_matches.get_one("timeout").copied().unwrap(),
+                compare: compare_options(sub_matches),
+            };
+            if std::io::stdout().is_terminal() && std::io::stderr().is_terminal() {
+                progress::enable();
+            }
+            match stress::stress(solution, brute, &options).await {
+                Ok(true) => exit_code::PASSED,
+                Ok(false) => exit_code::FAILED,
+                Err(e) => {
+                    eprintln!("Stress testing failed: {e:#}");
+                    exit_code::OTHER_ERROR
+                }
+            }
+        }
+        "languages" => {
+            print_languages();
+            0
+        }
+        "open" => {
+            let problem_name = match sub_matches.get_one::<String>("PROBLEM") {
+                Some(problem_name) => problem_name.clone(),
+                None => match checker::find_newest_source(Path::new("."), None, None) {
+                    Ok(source) => source.problem_name,
+                    Err(e) => {
+                        eprintln!("{e:#}");
+                        return 1;
+                    }
+                },
+            };
+            let url = link::problem_url(&problem_name);
+            println!("Opening {}", link::display_link(&url));
+            match open::that(&url) {
+                Ok(()) => 0,
+                Err(e) => {
+                    eprintln!("Failed to open the browser: {e}");
+                    1
+                }
+            }
+        }
+        "list-cache" | "clear-cache" => run_cache_subcommand(name),
+        _ => unreachable!("Unknown subcommand {name}"),
+    }
 }
 
 /// # Panics
 /// Panics if something goes wrong.
 #[tokio::main(flavor = "multi_thread", worker_threads = 2)]
+#[allow(clippy::too_many_lines)]
 pub async fn main() {
-    if std::env::var("RUST_LOG").is_err() {
-        std::env::set_var("RUST_LOG", "warn");
+    // Without RUST_LOG, the level is only lowered once --verbose has been parsed, so the logger
+    // lets everything from this crate through and the max level does the filtering
+    let log_filter = std::env::var("RUST_LOG").ok();
+    pretty_env_logger::formatted_builder()
+        .parse_filters(log_filter.as_deref().unwrap_or("warn,kattis=debug"))
+        .init();
+    if log_filter.is_none() {
+        log::set_max_level(LevelFilter::Warn);
     }
-    pretty_env_logger::init();
+    interrupt::listen();
     // Create folder in tmp if it doesn't already exist
     if let Err(e) = fetch::initialize_temp_dir() {
         eprintln!("{e}");
     }
 
-    let mut app = build_cli();
+    let config = match Config::load() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{e:#}");
+            std::process::exit(1);
+        }
+    };
+    let mut app = config.apply_defaults(build_cli());
 
     let matches = app.get_matches_mut();
-    let force_flag: bool = matches.get_one("force").copied().unwrap_or(false);
-    let submit_flag: bool = matches.get_one("submit").copied().unwrap_or(false);
+    configure_terminal(&matches, log_filter.is_none());
+    if let Err(e) = configure_network(&matches) {
+        eprintln!("{e:#}");
+        std::process::exit(exit_code::OTHER_ERROR);
+    }
     let recurse_depth: usize = matches.get_one("recurse").copied().unwrap_or(0);
-    let submission_viewer: viewer::SubmissionViewerType =
-        matches.get_one("submission-viewer").copied().unwrap();
+    RECURSE_DEPTH.set(recurse_depth).unwrap();
+    // test-all only finds the sources differently, so it goes through the same checks below
+    if let Some((name, sub_matches)) = matches.subcommand().filter(|(name, _)| *name != "test-all")
+    {
+        std::process::exit(run_subcommand(name, sub_matches, &config).await);
+    }
 
+    let submit_flag: bool = matches.get_one("submit").copied().unwrap_or(false);
+    let no_cache_flag: bool = matches.get_one("no-cache").copied().unwrap_or(false);
+    let keep_binary_flag: bool = matches.get_one("keep-binary").copied().unwrap_or(false);
+    let show_warnings_flag: bool = matches.get_one("show-warnings").copied().unwrap_or(false);
+    let python_impl: PythonImpl = matches.get_one("python-impl").copied().unwrap();
+    let cpp_compiler: CppCompiler = matches.get_one("compiler").copied().unwrap();
     if matches!(
         matches.value_source("submission-viewer"),
         Some(ValueSource::CommandLine)
-    ) && !submit_flag
+    ) && !matches.contains_id("submitting")
     {
         warn!(
             "{0} flag is set but {1} is not. Ignoring {0}.",
@@ -110,7 +884,6 @@ pub async fn main() {
         );
     }
 
-    RECURSE_DEPTH.set(recurse_depth).unwrap();
     info!("Recursing {recurse_depth} levels into directories.");
 
     let problem_args: Vec<&str> = matches
@@ -118,58 +891,473 @@ pub async fn main() {
         .unwrap_or_default()
         .map(String::as_str)
         .collect();
+    let problem_override = matches.get_one::<String>("problem").map(String::as_str);
+    if problem_override.is_some() && problem_args.len() > 1 {
+        eprintln!(
+            "{} can only be used with a single source file, since all of them would be tested against the same problem.",
+            "--problem".bold()
+        );
+        std::process::exit(1);
+    }
+    let lang = matches
+        .get_one::<String>("lang")
+        .map(|ext| Lang::from_extension(ext).unwrap());
+    let test_all_dir = matches
+        .subcommand_matches("test-all")
+        .map(|sub_matches| sub_matches.get_one::<PathBuf>("DIR").unwrap().as_path());
 
-    let problem_sources: Vec<ProblemSource> = {
-        if problem_args.is_empty() {
-            // Look for newest source file
-            match checker::find_newest_source() {
-                Ok(problem_source) => vec![problem_source],
-                Err(e) => {
-                    eprintln!(
-                        "Although kattis can be used without problem name arguments, \
-                        this requires the latest edited file in this directory to be a kattis source code file.\
-                        \nEncountered error: {e}\n\
-                        Perhaps you wanted the regular usage?"
-                    );
-                    eprintln!("{}", app.render_help());
-                    std::process::exit(1);
-                }
-            }
-        } else {
-            // Use the source files specified
-            problem_args
-                .into_iter()
-                .map(Path::new)
-                .map(find_source_from_path)
-                .collect::<Result<Vec<_>>>()
-                .context("Failed to find source files.")
-                .unwrap()
-        }
+    let problem_sources = if let Some(dir) = test_all_dir {
+        all_problem_sources(
+            dir,
+            problem_args.is_empty() && problem_override.is_none(),
+            lang.as_ref(),
+        )
+    } else {
+        // A single case given on the command line doesn't need the problem from Kattis
+        let check_exists = !matches.contains_id("input");
+        problem_sources(
+            &mut app,
+            problem_args,
+            problem_override,
+            lang.as_ref(),
+            check_exists,
+        )
     };
+    let extra_sources = extra_sources(&matches, problem_sources.len());
+    let gcc_bin = matches.get_one::<String>("gcc-bin").cloned();
+    let rustc_bin = matches.get_one::<String>("rustc-bin").cloned();
+    // Compilers that weren't chosen by the user fail the problems that need them while compiling
+    let chosen_cpp_compiler = gcc_bin.as_deref().or_else(|| {
+        matches!(
+            matches.value_source("compiler"),
+            Some(ValueSource::CommandLine)
+        )
+        .then(|| cpp_compiler.command())
+    });
+    check_compilers(&problem_sources, chosen_cpp_compiler, rustc_bin.as_deref()).await;
 
     let problems: Vec<Problem> = problem_sources
         .into_iter()
-        .map(Problem::new)
+        .map(|source| {
+            let extra_flags = config.extra_flags(&source.lang);
+            Problem::new(source).set_extra_flags(extra_flags)
+        })
         .map(|problem| problem.set_submit(submit_flag))
+        .map(|problem| problem.set_use_cache(!no_cache_flag))
+        .map(|problem| problem.set_keep_binary(keep_binary_flag))
+        .map(|problem| problem.set_show_warnings(show_warnings_flag))
+        .map(|problem| problem.set_python_impl(python_impl))
+        .map(|problem| problem.set_cpp_compiler(cpp_compiler))
+        .map(|problem| problem.set_cpp_bin(gcc_bin.clone()))
+        .map(|problem| problem.set_rustc_bin(rustc_bin.clone()))
+        .map(|problem| problem.set_extra_sources(extra_sources.clone()))
         .collect();
 
-    let mut failed_any: bool = false;
-    checker::check_problems(problems, force_flag, submission_viewer)
+    let options = check_options(&matches);
+    let output = options.output;
+    configure_output(&options);
+
+    let results = checker::check_problems(problems, options).await;
+    let exit_code = report_results(results, output);
+
+    std::process::exit(exit_code);
+}
+
+/// Prints a table of the recognized file extensions
+fn print_languages() {
+    let rows = all::<Lang>()
+        .map(|lang| {
+            [
+                lang.to_string(),
+                format!(".{}", lang.extension()),
+                if lang.compiled() { "yes" } else { "no" }.to_string(),
+                lang.run_command().to_string(),
+            ]
+        })
+        .collect_vec();
+    let header = ["Language", "Extension", "Compiled", "Run with"];
+    let widths = (0..header.len())
+        .map(|i| {
+            rows.iter()
+                .map(|row| row[i].chars().count())
+                .chain(std::iter::once(header[i].len()))
+                .max()
+                .unwrap_or_default()
+        })
+        .collect_vec();
+    let format_row = |row: &[&str]| {
+        row.iter()
+            .zip(&widths)
+            .map(|(column, width)| format!("{column:<width$}"))
+            .join("  ")
+            .trim_end()
+            .to_string()
+    };
+    println!("{}", format_row(&header).bold());
+    for row in &rows {
+        println!("{}", format_row(&row.each_ref().map(String::as_str)));
+    }
+}
+
+/// Runs `list-cache` or `clear-cache`
+fn run_cache_subcommand(name: &str) -> i32 {
+    if name == "list-cache" {
+        return match print_cache() {
+            Ok(()) => 0,
+            Err(e) => {
+                eprintln!("Failed to read the cache: {e}");
+                1
+            }
+        };
+    }
+    match fetch::clear_cache() {
+        Ok((files, bytes)) => {
+            println!(
+                "Removed {files} files, freeing {}.",
+                fetch::format_size(bytes)
+            );
+            0
+        }
+        Err(e) => {
+            eprintln!("Failed to clear the cache: {e}");
+            1
+        }
+    }
+}
+
+/// Prints the problems in the sample cache and the space they take up
+fn print_cache() -> Result<()> {
+    let problems = fetch::cached_problems()?;
+    if problems.is_empty() {
+        println!("No samples are cached.");
+        return Ok(());
+    }
+    let width = problems
+        .iter()
+        .map(|problem| problem.name.chars().count())
+        .chain(std::iter::once("Problem".len()))
+        .max()
+        .unwrap_or_default();
+    println!(
+        "{}",
+        format!("{:<width$}  {:>10}  Age", "Problem", "Size").bold()
+    );
+    for problem in &problems {
+        println!(
+            "{:<width$}  {:>10}  {}",
+            problem.name,
+            fetch::format_size(problem.bytes),
+            fetch::format_age(problem.age)
+        );
+    }
+    let total: u64 = problems.iter().map(|problem| problem.bytes).sum();
+    println!(
+        "{} problem{}, {} in total",
+        problems.len(),
+        if problems.len() == 1 { "" } else { "s" },
+        fetch::format_size(total)
+    );
+    Ok(())
+}
+
+/// The sources under `dir` for test-all. Exits if there are none, or if sources were also given
+/// on the command line.
+fn all_problem_sources(
+    dir: &Path,
+    no_other_sources: bool,
+    lang: Option<&Lang>,
+) -> Vec<ProblemSource> {
+    if !no_other_sources {
+        eprintln!(
+            "{} can't be combined with source files or {}.",
+            "test-all".bold(),
+            "--problem".bold()
+        );
+        std::process::exit(1);
+    }
+    checker::find_all_sources(dir, lang).unwrap_or_else(|e| {
+        eprintln!("{e:#}");
+        std::process::exit(error_exit_code(&e));
+    })
+}
+
+/// The sources named on the command line, or else the newest source in the working directory.
+/// Exits if they can't be found.
+/// With `check_exists`, named sources have to belong to a problem on Kattis.
+fn problem_sources(
+    app: &mut Command,
+    problem_args: Vec<&str>,
+    problem_override: Option<&str>,
+    lang: Option<&Lang>,
+    check_exists: bool,
+) -> Vec<ProblemSource> {
+    if problem_args.is_empty() {
+        // Look for newest source file
+        match checker::find_newest_source(Path::new("."), problem_override, lang) {
+            Ok(problem_source) => vec![problem_source],
+            Err(e) => {
+                eprintln!(
+                    "Although kattis can be used without problem name arguments, \
+                    this requires the latest edited file in this directory to be a kattis source code file.\
+                    \nEncountered error: {e}\n\
+                    Perhaps you wanted the regular usage?"
+                );
+                eprintln!("{}", app.render_help());
+                std::process::exit(1);
+            }
+        }
+    } else {
+        // Use the source files specified
+        problem_args
+            .into_iter()
+            .map(Path::new)
+            .map(|path| {
+                if check_exists {
+                    find_source_from_path(path, problem_override, lang)
+                } else {
+                    checker::source_from_path(path, problem_override, lang)
+                }
+            })
+            .collect::<Result<Vec<_>>>()
+            .context("Failed to find source files.")
+            .unwrap_or_else(|e| {
+                eprintln!("{e:?}");
+                std::process::exit(error_exit_code(&e));
+            })
+    }
+}
+
+/// Checks the compilers that the user chose with flags, if the sources need them, see
+/// `check_compiler`. A missing default compiler only fails the problems that need it.
+async fn check_compilers(
+    sources: &[ProblemSource],
+    cpp_compiler: Option<&str>,
+    rustc_bin: Option<&str>,
+) {
+    if let Some(cpp_compiler) = cpp_compiler {
+        if sources.iter().any(|source| source.lang == Lang::Cpp) {
+            check_compiler(cpp_compiler, "The C++ compiler", "--compiler or --gcc-bin").await;
+        }
+    }
+    if let Some(rustc_bin) = rustc_bin {
+        if sources.iter().any(|source| source.lang == Lang::Rust) {
+            check_compiler(rustc_bin, "The Rust compiler", "--rustc-bin").await;
+        }
+    }
+}
+
+/// Exits if `compiler` is not on the PATH, since the user asked for it. Its version is logged to
+/// confirm that the right toolchain is used.
+async fn check_compiler(compiler: &str, description: &str, flags: &str) {
+    if !checker::in_path(compiler) {
+        eprintln!(
+            "{description} {} was not found. Install it or choose another one with {}.",
+            compiler.bold(),
+            flags.bold()
+        );
+        std::process::exit(exit_code::OTHER_ERROR);
+    }
+    match tokio::process::Command::new(compiler)
+        .arg("--version")
+        .output()
         .await
-        .into_iter()
-        .for_each(|(problem, res)| match res {
-            Err(e) => eprintln!("Failed to check problem {}: {e}", problem.problem_name),
-            Ok(b) if !b => failed_any = true,
-            _ => {}
+    {
+        Ok(output) => info!(
+            "Using {compiler}: {}",
+            String::from_utf8_lossy(&output.stdout)
+                .lines()
+                .next()
+                .unwrap_or_default()
+        ),
+        Err(e) => warn!("Failed to get the version of {compiler}: {e}"),
+    }
+}
+
+/// Applies `--no-color` and, unless `RUST_LOG` decides the log level, `--verbose`
+fn configure_terminal(matches: &ArgMatches, set_log_level: bool) {
+    if matches.get_flag("no-color") || std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty()) {
+        colored::control::set_override(false);
+    }
+    if set_log_level {
+        log::set_max_level(match matches.get_count("verbose") {
+            0 => LevelFilter::Warn,
+            1 => LevelFilter::Info,
+            _ => LevelFilter::Debug,
         });
+    }
+}
+
+/// Sets the Kattis host and the proxy, checking that the proxy URL is valid before any request
+fn configure_network(matches: &ArgMatches) -> Result<()> {
+    HOST.set(matches.get_one::<String>("host").unwrap().clone())
+        .unwrap();
+    if let Some(proxy) = matches.get_one::<String>("proxy") {
+        PROXY.set(proxy.clone()).unwrap();
+    }
+    proxy().map(drop)
+}
+
+fn configure_output(options: &CheckOptions) {
+    if options.output == OutputFormat::Json {
+        // Keep escape codes out of the JSON strings
+        colored::control::set_override(false);
+    } else if !options.quiet && std::io::stdout().is_terminal() && std::io::stderr().is_terminal() {
+        progress::enable();
+    }
+}
+
+/// Files given with --with, exiting if they can't be used
+fn extra_sources(matches: &ArgMatches, source_count: usize) -> Vec<PathBuf> {
+    let extra_sources: Vec<PathBuf> = matches
+        .get_many::<PathBuf>("with")
+        .unwrap_or_default()
+        .cloned()
+        .collect();
+    if !extra_sources.is_empty() && source_count > 1 {
+        eprintln!(
+            "{} can only be used with a single source file.",
+            "--with".bold()
+        );
+        std::process::exit(1);
+    }
+    if let Some(missing) = extra_sources.iter().find(|path| !path.is_file()) {
+        eprintln!("Could not find the file {}", missing.display());
+        std::process::exit(1);
+    }
+    extra_sources
+}
+
+/// Errors caused by Kattis not being reachable are fetch errors
+fn error_exit_code(e: &anyhow::Error) -> i32 {
+    if e.chain().any(<dyn std::error::Error>::is::<reqwest::Error>) {
+        exit_code::FETCH_ERROR
+    } else {
+        exit_code::OTHER_ERROR
+    }
+}
+
+/// Prints the results in the requested format and returns the exit code for them
+fn report_results(results: Vec<(Problem, Result<ProblemReport>)>, output: OutputFormat) -> i32 {
+    let total = results.len();
+    let (mut passed, mut failed, mut compile_errors, mut errors) = (0, 0, 0, 0);
+    let (mut cancelled, mut no_samples) = (0, 0);
+    let mut exit_code = exit_code::PASSED;
+    let mut reports = Vec::new();
+    for (problem, res) in results {
+        match res {
+            Err(e) => {
+                exit_code = exit_code.max(error_exit_code(&e));
+                if output == OutputFormat::Json {
+                    reports.push(serde_json::json!({
+                        "problem": problem.problem_name,
+                        "error": e.to_string(),
+                    }));
+                } else {
+                    eprintln!("Failed to check problem {}: {e}", problem.problem_name);
+                }
+                errors += 1;
+            }
+            Ok(report) => {
+                let code = match report.outcome {
+                    Outcome::Passed | Outcome::Submitted => {
+                        passed += 1;
+                        exit_code::PASSED
+                    }
+                    Outcome::Failed => {
+                        failed += 1;
+                        if report.crashed() {
+                            exit_code::RUNTIME_ERROR
+                        } else {
+                            exit_code::FAILED
+                        }
+                    }
+                    Outcome::CompileError => {
+                        compile_errors += 1;
+                        exit_code::COMPILE_ERROR
+                    }
+                    Outcome::Cancelled => {
+                        cancelled += 1;
+                        exit_code::PASSED
+                    }
+                    Outcome::NoSamples => {
+                        no_samples += 1;
+                        exit_code::FAILED
+                    }
+                };
+                exit_code = exit_code.max(code);
+                if output == OutputFormat::Json {
+                    reports.push(serde_json::to_value(report).unwrap());
+                }
+            }
+        }
+    }
+
+    if output == OutputFormat::Json {
+        println!("{}", serde_json::to_string_pretty(&reports).unwrap());
+    } else if total > 1 {
+        print_summary(
+            total,
+            passed,
+            failed,
+            compile_errors,
+            errors,
+            cancelled,
+            no_samples,
+        );
+    }
 
-    std::process::exit(i32::from(failed_any));
+    exit_code
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::checker::{self, Lang, Outcome, Problem, ProblemSource};
+    use crate::{build_cli, check_options, fetch};
+    use std::fs;
+
     #[test]
     fn it_works() {
         assert_eq!("2".parse::<usize>().unwrap() + 2, 4);
     }
+
+    #[tokio::test]
+    async fn missing_compiler_fails_only_its_problem() {
+        fetch::initialize_temp_dir().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let file = |name: &str, contents: &str| {
+            let path = dir.path().join(name);
+            fs::write(&path, contents).unwrap();
+            path
+        };
+        let input = file("1.in", "");
+        let answer = file("1.ans", "Hello World!\n");
+        let problem = |path, lang| {
+            Problem::new(ProblemSource {
+                problem_name: "hello".to_string(),
+                path,
+                lang,
+            })
+            .set_use_cache(false)
+        };
+        let problems = vec![
+            problem(file("hello.cpp", "int main() {}\n"), Lang::Cpp)
+                .set_cpp_bin(Some("kattis-missing-compiler".to_string())),
+            problem(file("hello.sh", "echo Hello World!\n"), Lang::Bash),
+        ];
+        let matches = build_cli().get_matches_from([
+            "kattis".as_ref(),
+            "--input".as_ref(),
+            input.as_os_str(),
+            "--expected".as_ref(),
+            answer.as_os_str(),
+        ]);
+
+        let results = checker::check_problems(problems, check_options(&matches)).await;
+        let Err(error) = &results[0].1 else {
+            panic!("{} compiled without a compiler", results[0].0.problem_name);
+        };
+        assert!(format!("{error:#}").contains("kattis-missing-compiler"));
+        let report = results[1].1.as_ref().unwrap();
+        assert!(matches!(report.outcome, Outcome::Passed));
+    }
 }
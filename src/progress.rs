@@ -0,0 +1,100 @@
+use crate::compare::terminal_width;
+use itertools::Itertools;
+use std::io::Write;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, Once};
+use std::time::Duration;
+
+const FRAMES: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+
+/// Steps that are in progress, and whether the spinner line is currently on the screen
+struct State {
+    enabled: bool,
+    tasks: Vec<(usize, String)>,
+    drawn: bool,
+}
+
+static STATE: Mutex<State> = Mutex::new(State {
+    enabled: false,
+    tasks: Vec::new(),
+    drawn: false,
+});
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+static TICKER: Once = Once::new();
+
+impl State {
+    fn clear(&mut self) {
+        if self.drawn {
+            eprint!("\r\x1b[K");
+            std::io::stderr().flush().ok();
+            self.drawn = false;
+        }
+    }
+
+    fn draw(&mut self, frame: char) {
+        if self.tasks.is_empty() {
+            self.clear();
+            return;
+        }
+        let line = format!(
+            "{frame} {}",
+            self.tasks.iter().map(|(_, message)| message).join(", ")
+        );
+        let line: String = line
+            .chars()
+            .take(terminal_width().saturating_sub(1))
+            .collect();
+        eprint!("\r\x1b[K{line}");
+        std::io::stderr().flush().ok();
+        self.drawn = true;
+    }
+}
+
+/// Shows a spinner on stderr for the steps started from now on. Only meant for terminals.
+pub fn enable() {
+    STATE.lock().unwrap().enabled = true;
+    TICKER.call_once(|| {
+        std::thread::spawn(|| {
+            for frame in FRAMES.iter().cycle() {
+                std::thread::sleep(Duration::from_millis(100));
+                STATE.lock().unwrap().draw(*frame);
+            }
+        });
+    });
+}
+
+/// A step shown next to the spinner until it is dropped
+pub struct Task(Option<usize>);
+
+impl Drop for Task {
+    fn drop(&mut self) {
+        let Some(id) = self.0 else {
+            return;
+        };
+        let mut state = STATE.lock().unwrap();
+        state.tasks.retain(|(task_id, _)| *task_id != id);
+        state.clear();
+    }
+}
+
+/// Shows `message` next to the spinner while the returned task is alive
+pub fn start(message: impl Into<String>) -> Task {
+    let mut state = STATE.lock().unwrap();
+    if !state.enabled {
+        return Task(None);
+    }
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    state.tasks.push((id, message.into()));
+    drop(state);
+    Task(Some(id))
+}
+
+/// Removes the spinner while `print` writes to the terminal, so the output doesn't end up on
+/// the spinner line
+pub fn suspend<R>(print: impl FnOnce() -> R) -> R {
+    let mut state = STATE.lock().unwrap();
+    state.clear();
+    let result = print();
+    drop(state);
+    result
+}
@@ -0,0 +1,156 @@
+use crate::checker::Lang;
+use crate::fetch;
+use anyhow::{bail, Context, Result};
+use colored::Colorize;
+use log::info;
+use std::path::{Path, PathBuf};
+
+/// Skeleton that reads all of stdin, used when there is no user template for the language
+const fn default_template(lang: &Lang) -> &'static str {
+    match lang {
+        Lang::C => {
+            "#include <stdio.h>
+
+int main(void) {
+    int n;
+    scanf(\"%d\", &n);
+
+    return 0;
+}
+"
+        }
+        Lang::Cpp => {
+            "#include <bits/stdc++.h>
+using namespace std;
+
+int main() {
+    ios::sync_with_stdio(false);
+    cin.tie(nullptr);
+
+    int n;
+    cin >> n;
+
+    return 0;
+}
+"
+        }
+        Lang::Rust => {
+            "use std::io::{self, Read};
+
+fn main() {
+    let mut input = String::new();
+    io::stdin().read_to_string(&mut input).unwrap();
+    let mut tokens = input.split_ascii_whitespace();
+}
+"
+        }
+        Lang::Kotlin => {
+            "fun main() {
+    val lines = generateSequence(::readLine).toList()
+}
+"
+        }
+        Lang::CSharp => {
+            "using System;
+
+public class Program
+{
+    public static void Main()
+    {
+        var input = Console.In.ReadToEnd().Split((char[])null, StringSplitOptions.RemoveEmptyEntries);
+    }
+}
+"
+        }
+        Lang::Haskell => {
+            "main :: IO ()
+main = do
+  input <- getContents
+  let ls = lines input
+  return ()
+"
+        }
+        Lang::Go => {
+            "package main
+
+import (
+\t\"bufio\"
+\t\"fmt\"
+\t\"os\"
+)
+
+func main() {
+\treader := bufio.NewReader(os.Stdin)
+\twriter := bufio.NewWriter(os.Stdout)
+\tdefer writer.Flush()
+
+\tvar n int
+\tfmt.Fscan(reader, &n)
+}
+"
+        }
+        Lang::Python => {
+            "import sys
+
+
+def main():
+    data = sys.stdin.read().split()
+
+
+if __name__ == \"__main__\":
+    main()
+"
+        }
+        Lang::JavaScript => {
+            "const input = require(\"fs\").readFileSync(0, \"utf8\").split(\"\\n\");
+"
+        }
+        Lang::Ruby => {
+            "input = $stdin.read.split
+"
+        }
+        Lang::Bash => {
+            "#!/usr/bin/env bash
+
+read -r n
+"
+        }
+    }
+}
+
+/// Reads `template.<ext>` from the template directory, falling back to the built-in skeleton
+fn template(lang: &Lang, template_dir: Option<&Path>) -> Result<String> {
+    if let Some(template_dir) = template_dir {
+        let path = template_dir.join(format!("template.{}", lang.extension()));
+        if path.is_file() {
+            info!("Using template {}", path.display());
+            return std::fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read template {}", path.display()));
+        }
+    }
+    Ok(default_template(lang).to_string())
+}
+
+/// Creates `<problem>.<ext>` in the current directory from a template, and optionally downloads
+/// the samples so they are cached before the first run
+pub async fn new_problem(
+    problem_name: &str,
+    lang: &Lang,
+    template_dir: Option<&Path>,
+    fetch_samples: bool,
+) -> Result<PathBuf> {
+    let path = PathBuf::from(format!("{problem_name}.{}", lang.extension()));
+    if path.exists() {
+        bail!("{} already exists", path.display());
+    }
+
+    std::fs::write(&path, template(lang, template_dir)?)
+        .with_context(|| format!("Failed to create {}", path.display()))?;
+    println!("Created {}", path.display().to_string().bold());
+
+    if fetch_samples {
+        let samples = fetch::problem(problem_name, None).await?;
+        println!("Fetched {} sample cases", samples.len());
+    }
+    Ok(path)
+}
@@ -0,0 +1,131 @@
+use crate::checker::{Lang, ProblemSource, Program};
+use crate::compare::{compare, CompareOptions};
+use crate::{fetch, interrupt, progress};
+use anyhow::{anyhow, bail, Context, Result};
+use colored::Colorize;
+use futures::try_join;
+use itertools::Itertools;
+use std::path::Path;
+use std::time::Duration;
+use tokio::process::Command;
+
+/// Lines of a failing input that are printed, the full input is saved to a file
+const MAX_INPUT_LINES: usize = 50;
+
+pub struct StressOptions {
+    /// Command that writes an input to stdout. The test number is added as its last argument, to
+    /// be used as a seed.
+    pub generator: String,
+    pub iterations: usize,
+    /// Time limit for each run of the generator and the solutions
+    pub timeout: Duration,
+    pub compare: CompareOptions,
+}
+
+/// Compiles a solution, which doesn't have to be named after a problem since no samples are used
+async fn compile(path: &Path) -> Result<Program> {
+    let lang = path
+        .extension()
+        .and_then(|ext| Lang::from_extension(ext.to_string_lossy()))
+        .ok_or_else(|| {
+            anyhow!(
+                "{} is not a source file in a supported language",
+                path.display()
+            )
+        })?;
+    let mut program = Program::from_problem_source(ProblemSource {
+        problem_name: path
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string(),
+        path: path.to_path_buf(),
+        lang,
+    });
+    program.compile().await?;
+    if let Some(compile_error) = program.compile_error() {
+        bail!("{} failed to compile:\n{compile_error}", program.name());
+    }
+    Ok(program)
+}
+
+/// Runs the generator with `seed` and writes the input it prints to `input`
+async fn generate(options: &StressOptions, seed: usize, input: &Path) -> Result<()> {
+    let mut words = options.generator.split_whitespace();
+    let generator = words
+        .next()
+        .ok_or_else(|| anyhow!("The generator command is empty"))?;
+    let mut command = Command::new(generator);
+    command.args(words).arg(seed.to_string()).kill_on_drop(true);
+    let output = interrupt::output(&mut command);
+    let output = tokio::time::timeout(options.timeout, output)
+        .await
+        .map_err(|_| anyhow!("The generator timed out with seed {seed}"))?
+        .with_context(|| format!("Failed to run the generator {generator}"))?;
+    if !output.status.success() {
+        bail!(
+            "The generator failed with seed {seed}:\n{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    std::fs::write(input, output.stdout)?;
+    Ok(())
+}
+
+/// Runs `solution` and `brute` on generated inputs until their outputs differ, using the output
+/// of `brute` as the answer. Returns whether every test passed.
+pub async fn stress(solution: &Path, brute: &Path, options: &StressOptions) -> Result<bool> {
+    let (solution, brute) = try_join!(compile(solution), compile(brute))?;
+    let input = fetch::temp_file()?.into_temp_path();
+
+    for seed in 1..=options.iterations {
+        let progress = progress::start(format!("Stress test {seed}/{}…", options.iterations));
+        generate(options, seed, &input).await?;
+        let answer = match brute.output_for(&input, options.timeout).await? {
+            Ok(answer) => answer,
+            Err(result) => bail!(
+                "{} failed with seed {seed}, so there is no answer to compare with:\n{}",
+                brute.name(),
+                result.display(brute.name())
+            ),
+        };
+        let failure = match solution.output_for(&input, options.timeout).await? {
+            Ok(output) => {
+                let result = compare(&output, &answer, &options.compare);
+                result.failed.is_some().then(|| format!("{result}\n"))
+            }
+            Err(result) => Some(result.display(solution.name())),
+        };
+        drop(progress);
+
+        let Some(failure) = failure else {
+            continue;
+        };
+        let contents = std::fs::read_to_string(&input)?;
+        let saved = fetch::kattis_dir().join(format!("stress-{seed}.in"));
+        input.persist(&saved)?;
+        let lines = contents.lines().count();
+        println!(
+            "{}",
+            format!("{} failed with seed {seed}", solution.name())
+                .red()
+                .bold()
+        );
+        println!("{}", "Input:".bold());
+        println!("{}", contents.lines().take(MAX_INPUT_LINES).join("\n"));
+        if lines > MAX_INPUT_LINES {
+            println!("... ({} more lines)", lines - MAX_INPUT_LINES);
+        }
+        println!("Saved the input to {}", saved.display());
+        print!("{}\n{failure}", "Result:".bold());
+        return Ok(false);
+    }
+
+    println!(
+        "{}",
+        format!("All {} stress tests passed", options.iterations)
+            .green()
+            .bold()
+    );
+    Ok(true)
+}
@@ -0,0 +1,126 @@
+use anyhow::{anyhow, Result};
+use colored::Colorize;
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// A submission made with this tool
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Record {
+    /// Seconds since the Unix epoch
+    pub timestamp: u64,
+    pub problem: String,
+    pub language: String,
+    pub submission_id: String,
+    /// Final judgement, if the submission was followed until it was judged
+    pub status: Option<String>,
+}
+
+impl Record {
+    pub fn new(problem: &str, language: &str, submission_id: &str, status: Option<String>) -> Self {
+        Self {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |since_epoch| since_epoch.as_secs()),
+            problem: problem.to_string(),
+            language: language.to_string(),
+            submission_id: submission_id.to_string(),
+            status,
+        }
+    }
+}
+
+fn history_path() -> Result<PathBuf> {
+    dirs::data_dir()
+        .map(|dir| dir.join("kattis-rs/history.jsonl"))
+        .ok_or_else(|| {
+            anyhow!("Could not find a data directory to store the submission history in")
+        })
+}
+
+/// Appends a submission to the history file, one JSON object per line
+pub fn append(record: &Record) -> Result<()> {
+    let path = history_path()?;
+    std::fs::create_dir_all(path.parent().unwrap())?;
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{}", serde_json::to_string(record)?)?;
+    Ok(())
+}
+
+/// The last `count` submissions, oldest first
+fn last(count: usize) -> Result<Vec<Record>> {
+    let path = history_path()?;
+    if !path.is_file() {
+        return Ok(Vec::new());
+    }
+    let contents = std::fs::read_to_string(path)?;
+    let lines: Vec<&str> = contents
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .collect();
+    lines[lines.len().saturating_sub(count)..]
+        .iter()
+        .map(|line| serde_json::from_str(line).map_err(Into::into))
+        .collect()
+}
+
+/// Prints the last `count` submissions as a table
+pub fn print(count: usize) -> Result<()> {
+    let records = last(count)?;
+    if records.is_empty() {
+        println!("No submissions yet.");
+        return Ok(());
+    }
+
+    let rows: Vec<[String; 5]> = records
+        .iter()
+        .map(|record| {
+            let time = UNIX_EPOCH + Duration::from_secs(record.timestamp);
+            [
+                humantime::format_rfc3339_seconds(time)
+                    .to_string()
+                    .replace('T', " ")
+                    .replace('Z', ""),
+                record.problem.clone(),
+                record.language.clone(),
+                record.submission_id.clone(),
+                record.status.clone().unwrap_or_else(|| "-".to_string()),
+            ]
+        })
+        .collect();
+    let header = ["Time (UTC)", "Problem", "Language", "Submission", "Status"];
+    let widths: Vec<usize> = (0..header.len())
+        .map(|i| {
+            rows.iter()
+                .map(|row| row[i].chars().count())
+                .chain(std::iter::once(header[i].len()))
+                .max()
+                .unwrap_or_default()
+        })
+        .collect();
+
+    let header_line = header
+        .iter()
+        .zip(&widths)
+        .map(|(title, width)| format!("{title:<width$}"))
+        .join("  ");
+    println!("{}", header_line.trim_end().bold());
+    for row in rows {
+        let (status, columns) = row.split_last().unwrap();
+        let line = columns
+            .iter()
+            .zip(&widths)
+            .map(|(column, width)| format!("{column:<width$}"))
+            .join("  ");
+        let status = match status.as_str() {
+            "Accepted" => status.green(),
+            "-" => status.normal(),
+            _ => status.red(),
+        };
+        println!("{line}  {status}");
+    }
+    Ok(())
+}
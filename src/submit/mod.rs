@@ -1,15 +1,18 @@
+use crate::{host, link, proxy};
 use anyhow::{anyhow, bail, Result};
 use colored::Colorize;
-use lazy_static::lazy_static;
+use itertools::Itertools;
 use regex::Regex;
 use reqwest::header;
 use reqwest::multipart;
 use std::collections::HashMap;
 use std::fmt::Debug;
 use std::io::{Error, ErrorKind};
+use std::sync::LazyLock;
 use tokio::fs::File;
 use tokio::io::AsyncReadExt;
 
+pub mod history;
 pub mod viewer;
 
 #[derive(Clone, Debug)]
@@ -28,25 +31,71 @@ impl KattisConfig {
         let mut read_setting =
             |first, second| -> Option<String> { config.get_mut(first)?.remove(second)? };
 
-        let mut read_setting_with_error = |first, second| -> Result<String> {
-            read_setting(first, second)
-                .ok_or_else(|| anyhow!("Failed to read {}.{} from .kattisrc", first, second))
+        let username = read_setting("user", "username");
+        let token = read_setting("user", "token");
+        let login_url = read_setting("kattis", "loginurl");
+        let submit_url = read_setting("kattis", "submissionurl");
+        // Older files don't list the submissions page, which is next to the submit page
+        let submissions_url = read_setting("kattis", "submissionsurl").or_else(|| {
+            let base = submit_url.as_deref()?.strip_suffix("/submit")?;
+            Some(format!("{base}/submissions"))
+        });
+
+        let (Some(username), Some(token), Some(login_url), Some(submit_url), Some(submissions_url)) =
+            (&username, &token, &login_url, &submit_url, &submissions_url)
+        else {
+            let missing = [
+                ("user.username", username.is_none()),
+                ("user.token", token.is_none()),
+                ("kattis.loginurl", login_url.is_none()),
+                ("kattis.submissionurl", submit_url.is_none()),
+                ("kattis.submissionsurl", submissions_url.is_none()),
+            ]
+            .into_iter()
+            .filter_map(|(key, is_missing)| is_missing.then_some(key))
+            .join(", ");
+            bail!(
+                "Your .kattisrc is missing {missing}. It might be outdated, \
+                please download a new one from {}",
+                kattisrc_link()
+            );
         };
 
         Ok(Self {
-            username: read_setting_with_error("user", "username")?,
-            token: read_setting_with_error("user", "token")?,
-            login_url: read_setting_with_error("kattis", "loginurl")?,
-            submit_url: read_setting_with_error("kattis", "submissionurl")?,
-            submissions_url: read_setting_with_error("kattis", "submissionsurl")?,
+            username: username.clone(),
+            token: token.clone(),
+            login_url: login_url.clone(),
+            submit_url: submit_url.clone(),
+            submissions_url: submissions_url.clone(),
         })
     }
 }
 
-lazy_static! {
-    static ref ID_RE: Regex = Regex::new(r"Submission ID: (\d+)").unwrap();
+impl KattisConfig {
+    /// Settings for submitting to `host` without a `.kattisrc`, e.g. `open.kattis.com`
+    fn from_env(username: String, token: String, host: &str) -> Self {
+        let host = host.trim_end_matches('/');
+        let base = if host.contains("://") {
+            host.to_string()
+        } else {
+            format!("https://{host}")
+        };
+        Self {
+            username,
+            token,
+            login_url: format!("{base}/login"),
+            submit_url: format!("{base}/submit"),
+            submissions_url: format!("{base}/submissions"),
+        }
+    }
+}
+
+fn kattisrc_link() -> String {
+    link::display_link(&format!("https://{}/download/kattisrc", host()))
 }
 
+static ID_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"Submission ID: (\d+)").unwrap());
+
 async fn get_config() -> Result<KattisConfig> {
     let mut rc = dirs::home_dir().ok_or_else(|| {
         Error::new(
@@ -67,9 +116,11 @@ async fn get_config() -> Result<KattisConfig> {
                 anyhow!("Failed to read .kattisrc file with error:\n{e}\nPerhaps it is corrupt?")
             })?;
         KattisConfig::from_config(config)
+    } else if let Some(config) = config_from_env()? {
+        Ok(config)
     } else {
         rc.pop();
-        let link = viewer::display_link("https://open.kattis.com/download/kattisrc");
+        let link = kattisrc_link();
         bail!(
             "\
 Failed to read in a config file from your home directory.
@@ -86,29 +137,81 @@ token: *********
 [kattis]
 loginurl: https://<kattis>/login
 submissionurl: https://<kattis>/submit
+
+Alternatively, set KATTIS_USERNAME and KATTIS_TOKEN, and KATTIS_HOST if you
+don't use {}.
         ",
-            rc.to_str().unwrap_or("[Failed to detect home directory]")
+            rc.to_str().unwrap_or("[Failed to detect home directory]"),
+            host()
         );
     }
 }
 
+/// Settings from `KATTIS_USERNAME`, `KATTIS_TOKEN` and `KATTIS_HOST`, for when there is no
+/// `.kattisrc`. The host defaults to the one given with --host.
+fn config_from_env() -> Result<Option<KattisConfig>> {
+    let var = |name| std::env::var(name).ok().filter(|value| !value.is_empty());
+    match (var("KATTIS_USERNAME"), var("KATTIS_TOKEN")) {
+        (Some(username), Some(token)) => {
+            let kattis_host = var("KATTIS_HOST").unwrap_or_else(|| host().to_string());
+            Ok(Some(KattisConfig::from_env(username, token, &kattis_host)))
+        }
+        (Some(_), None) => bail!("KATTIS_USERNAME is set, but KATTIS_TOKEN is not"),
+        (None, Some(_)) => bail!("KATTIS_TOKEN is set, but KATTIS_USERNAME is not"),
+        (None, None) => Ok(None),
+    }
+}
+
+/// Prints the fields and files that would be posted to Kattis
+fn print_dry_run(language: &str, problem: &str, mainclass: &str, files: &[(String, Vec<u8>)]) {
+    const PREVIEW_LINES: usize = 5;
+    eprintln!("{}", "Dry run, not submitting:".yellow().bold());
+    eprintln!("  language:  {language}");
+    eprintln!("  problem:   {problem}");
+    eprintln!("  mainclass: {mainclass}");
+    for (file_name, contents) in files {
+        let contents = String::from_utf8_lossy(contents);
+        eprintln!(
+            "  sub_file[]: {} ({} bytes)",
+            file_name.bold(),
+            contents.len()
+        );
+        for line in contents.lines().take(PREVIEW_LINES) {
+            eprintln!("    {line}");
+        }
+        let remaining = contents.lines().count().saturating_sub(PREVIEW_LINES);
+        if remaining > 0 {
+            eprintln!("    {}", format!("… {remaining} more lines").dimmed());
+        }
+    }
+}
+
 pub async fn submit(
     language: String,
     problem: String,
-    submission_filename: String,
-    submission: String,
+    mainclass: String, // The class with the entry point on the JVM, ignored for other languages
+    files: Vec<(String, Vec<u8>)>, // File name and contents, main file first
     submission_viewer: viewer::SubmissionViewerType,
+    poll: viewer::PollOptions,
+    dry_run: bool,
 ) -> Result<()> {
+    if dry_run {
+        print_dry_run(&language, &problem, &mainclass, &files);
+        return Ok(());
+    }
     let config = get_config().await?;
     let mut default_headers = header::HeaderMap::new();
     default_headers.insert(
         header::USER_AGENT,
         header::HeaderValue::from_static("kattis-cli-submit"),
     );
-    let client = reqwest::ClientBuilder::new()
+    let mut client = reqwest::ClientBuilder::new()
         .default_headers(default_headers)
-        .cookie_store(true)
-        .build()?;
+        .cookie_store(true);
+    if let Some(proxy) = proxy()? {
+        client = client.proxy(proxy);
+    }
+    let client = client.build()?;
 
     // Login
     let login_map = serde_json::json!({
@@ -117,62 +220,143 @@ pub async fn submit(
         "token": config.token.as_str(),
     });
 
-    let _login_response = client
+    let login_response = client
         .post(&config.login_url)
         .header("Content-Type", "application/x-www-form-urlencoded")
         .form(&login_map)
         .send()
         .await?;
+    let login_status = login_response.status();
+    if !login_status.is_success() {
+        bail!(
+            "Failed to log in to Kattis ({login_status}): {}",
+            response_message(&login_response.text().await?)
+        );
+    }
 
     // Make a submission
     let submission_map = serde_json::json!({
         "submit": "true",
         "submit_ctr": "2",
         "language": language,
-        "mainclass": problem,
+        "mainclass": mainclass,
         "problem": problem,
         "script": "true",
     });
 
     let mut form = multipart::Form::new();
     for (k, v) in submission_map.as_object().unwrap() {
-        form = form.text(k.to_string(), v.as_str().unwrap().to_string());
+        form = form.text(k.clone(), v.as_str().unwrap().to_string());
     }
 
-    let mut sub_file = multipart::Part::bytes(submission.as_bytes().to_owned())
-        .file_name(submission_filename.clone());
-    sub_file = sub_file.mime_str("application/octet-stream")?;
-
-    form = form.part("sub_file[]", sub_file);
+    let file_names = files.iter().map(|(name, _)| name.clone()).join(", ");
+    for (file_name, contents) in files {
+        let sub_file = multipart::Part::bytes(contents)
+            .file_name(file_name)
+            .mime_str("application/octet-stream")?;
+        form = form.part("sub_file[]", sub_file);
+    }
     let submission_response = client
         .post(&config.submit_url)
         .multipart(form)
         .send()
-        .await?
-        .text()
         .await?;
+    let submission_status = submission_response.status();
+    let submission_response = submission_response.text().await?;
 
     if let Some(submission_id) = ID_RE.captures(&submission_response) {
         use viewer::SubmissionViewerType::{Browser, Cli, None};
 
         let submission_id = submission_id.get(1).unwrap().as_str();
-        eprintln!(
-            "{}",
-            format!("Submitted {submission_filename}.").as_str().green()
-        );
-        match submission_viewer {
+        eprintln!("{}", format!("Submitted {file_names}.").as_str().green());
+        // The submission is recorded even if following it fails, only without a status
+        let followed = match submission_viewer {
             Browser => {
                 eprintln!("Opening submission in browser...");
-                open::that(format!("{}/{}", config.submissions_url, submission_id))?;
+                open::that(format!("{}/{}", config.submissions_url, submission_id))
+                    .map(|()| Option::None)
+                    .map_err(Into::into)
             }
             Cli => {
                 eprintln!();
-                viewer::view_submission_in_terminal(client, submission_id).await?;
+                viewer::view_submission_in_terminal(
+                    client,
+                    &config.submissions_url,
+                    submission_id,
+                    poll,
+                )
+                .await
+                .map(Some)
             }
-            None => {}
+            None => Ok(Option::None),
+        };
+
+        let status = followed.as_ref().ok().cloned().flatten();
+        let record = history::Record::new(&problem, &language, submission_id, status);
+        if let Err(e) = history::append(&record) {
+            log::warn!("Failed to save the submission to the history: {e}");
         }
-        Ok(())
+        followed.map(drop)
     } else {
-        bail!("Failed to read submission ID from submission response");
+        bail!(
+            "Kattis did not accept the {language} submission ({submission_status}): {}",
+            response_message(&submission_response)
+        );
+    }
+}
+
+/// The text of a response from Kattis without any HTML, shortened to fit in an error message
+fn response_message(response: &str) -> String {
+    const MAX_CHARS: usize = 300;
+    static TAG_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new("<[^>]*>").unwrap());
+    let message = TAG_RE
+        .replace_all(response, " ")
+        .split_whitespace()
+        .join(" ");
+    if message.is_empty() {
+        "No explanation was given".to_string()
+    } else if message.chars().count() > MAX_CHARS {
+        format!("{}…", message.chars().take(MAX_CHARS).collect::<String>())
+    } else {
+        message
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::submit::{response_message, KattisConfig};
+
+    fn parse(rc: &str) -> anyhow::Result<KattisConfig> {
+        KattisConfig::from_config(configparser::ini::Ini::new().read(rc.to_string()).unwrap())
+    }
+
+    #[test]
+    fn test_kattisrc() {
+        let config = parse(
+            "[user]\nusername: user\ntoken: abc\n\
+            [kattis]\nloginurl: https://open.kattis.com/login\nsubmissionurl: https://open.kattis.com/submit\n",
+        )
+        .unwrap();
+        assert_eq!(
+            config.submissions_url,
+            "https://open.kattis.com/submissions"
+        );
+
+        let error = parse("[user]\nusername: user\n").unwrap_err().to_string();
+        assert!(error
+            .contains("user.token, kattis.loginurl, kattis.submissionurl, kattis.submissionsurl"));
+
+        let config = KattisConfig::from_env("user".into(), "abc".into(), "open.kattis.com/");
+        assert_eq!(config.login_url, "https://open.kattis.com/login");
+        assert_eq!(config.submit_url, "https://open.kattis.com/submit");
+    }
+
+    #[test]
+    fn test_response_message() {
+        assert_eq!(
+            response_message("<p>Language <b>Pyton 3</b> is not allowed\nfor this problem.</p>"),
+            "Language Pyton 3 is not allowed for this problem."
+        );
+        assert_eq!(response_message("  \n"), "No explanation was given");
     }
 }
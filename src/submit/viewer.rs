@@ -1,35 +1,18 @@
+use crate::link::{display_link, display_link_with_name};
+use anyhow::bail;
 use clap::builder::{PossibleValue, TypedValueParser};
 use clap::{Arg, Command};
 use colored::{ColoredString, Colorize};
 use enum_iterator::Sequence;
-use lazy_static::lazy_static;
 use log::info;
 use regex::Regex;
-use reqwest::Client;
+use reqwest::{header, Client, StatusCode, Url};
 use serde::Deserialize;
 use serde_repr::Deserialize_repr;
 use std::ffi::OsStr;
 use std::fmt::{Display, Formatter};
-use std::io::IsTerminal;
 use std::sync::OnceLock;
-
-lazy_static! {
-    static ref X_MARK: ColoredString = "✘".red();
-    static ref CHECK_MARK: ColoredString = "✔".green();
-}
-
-pub fn display_link(url: &str) -> String {
-    display_link_with_name(url, url)
-}
-
-/// Add link if outputting to a terminal
-fn display_link_with_name(url: &str, name: &str) -> String {
-    if std::io::stdout().is_terminal() {
-        format!("\u{1b}]8;;{url}\u{1b}\\{name}\u{1b}]8;;\u{1b}\\")
-    } else {
-        name.to_string()
-    }
-}
+use std::time::{Duration, Instant};
 
 /// Adds link if `maybe_url` is `Some` and outputting to a terminal
 fn maybe_add_link(s: &str, maybe_url: Option<&str>) -> String {
@@ -41,10 +24,18 @@ struct SubmissionResponse {
     #[serde(rename = "status_id")]
     status: SubmissionStatus,
     testcase_index: usize,
-    // testdata_groups_html: String,
-    // feedback_html: String,
+    #[serde(default)]
+    testdata_groups_html: String,
+    #[serde(default)]
+    feedback_html: String,
     // judge_feedback_html: String,
     row_html: String,
+    /// Where the submission was made, for the links
+    #[serde(skip)]
+    submissions_url: String,
+    /// Scheme and host of the Kattis instance, for the links to the problem
+    #[serde(skip)]
+    origin: String,
 }
 
 impl SubmissionResponse {
@@ -67,6 +58,17 @@ impl SubmissionResponse {
         let re = RE.get_or_init(|| Regex::new("data-type=\"cpu\".*?>(.*?)&").unwrap());
         Some(re.captures(&self.row_html)?.get(1)?.as_str())
     }
+    /// Peak memory usage, e.g. `4 MB`
+    fn memory(&self) -> Option<String> {
+        static RE: OnceLock<Regex> = OnceLock::new();
+        let re = RE.get_or_init(|| Regex::new("data-type=\"mem\".*?>(.*?)<").unwrap());
+        let memory = re
+            .captures(&self.row_html)?
+            .get(1)?
+            .as_str()
+            .replace("&nbsp;", " ");
+        Some(memory.trim().to_string()).filter(|memory| !memory.is_empty())
+    }
     fn language(&self) -> Option<&str> {
         static RE: OnceLock<Regex> = OnceLock::new();
         let re = RE.get_or_init(|| Regex::new("data-type=\"lang\".*?>(.*?)<").unwrap());
@@ -77,11 +79,36 @@ impl SubmissionResponse {
         let re = RE.get_or_init(|| Regex::new("data-type=\"problem\".*?href=\"(.*?)\"").unwrap());
         Some(re.captures(&self.row_html)?.get(1)?.as_str())
     }
+    fn submission_link(&self) -> Option<String> {
+        self.submission_id()
+            .map(|id| format!("{}/{id}", self.submissions_url))
+    }
     fn submission_id(&self) -> Option<&str> {
         static RE: OnceLock<Regex> = OnceLock::new();
         let re = RE.get_or_init(|| Regex::new("data-submission-id=\"(.*?)\"").unwrap());
         Some(re.captures(&self.row_html)?.get(1)?.as_str())
     }
+    /// The first test group or case that wasn't accepted, e.g. `Test case 3/10`
+    fn failing_testcase(&self) -> Option<&str> {
+        static RE: OnceLock<Regex> = OnceLock::new();
+        let re = RE.get_or_init(|| {
+            Regex::new("title=\"(Test (?:case|group) [^\"]*?): ([^\"]*)\"").unwrap()
+        });
+        re.captures_iter(&self.testdata_groups_html)
+            .find(|captures| {
+                let verdict = captures[2].to_lowercase();
+                verdict != "accepted" && verdict != "not checked"
+            })
+            .map(|captures| captures.get(1).unwrap().as_str())
+    }
+    /// Feedback from the judge as plain text, which Kattis only gives for some cases
+    fn feedback(&self) -> Option<String> {
+        static RE: OnceLock<Regex> = OnceLock::new();
+        let re = RE.get_or_init(|| Regex::new("<[^>]*>").unwrap());
+        let feedback = re.replace_all(&self.feedback_html, " ");
+        let feedback = feedback.split_whitespace().collect::<Vec<_>>().join(" ");
+        (!feedback.is_empty()).then_some(feedback)
+    }
 }
 
 impl Display for SubmissionResponse {
@@ -98,9 +125,7 @@ impl Display for SubmissionResponse {
             )
         } else if self.status == SubmissionStatus::Accepted {
             let mut accepted_text: ColoredString = "Submission Accepted!".into();
-            let submission_link = self
-                .submission_id()
-                .map(|id| format!("https://open.kattis.com/submissions/{id}"));
+            let submission_link = self.submission_link();
             accepted_text = maybe_add_link(&accepted_text, submission_link.as_deref())
                 .green()
                 .bold();
@@ -113,7 +138,7 @@ impl Display for SubmissionResponse {
                     maybe_add_link(
                         problem_name,
                         self.problem_slug()
-                            .map(|slug| format!("https://open.kattis.com{slug}"))
+                            .map(|slug| format!("{}{slug}", self.origin))
                             .as_deref()
                     )
                     .bold()
@@ -123,13 +148,16 @@ impl Display for SubmissionResponse {
                 }
                 if let Some(time) = self.cpu_time() {
                     if let Some(slug) = self.problem_slug() {
-                        let url = format!("https://open.kattis.com{slug}/statistics");
+                        let url = format!("{}{slug}/statistics", self.origin);
                         let seconds_with_link = display_link_with_name(&url, &format!("{time}s"));
                         write!(f, " ran in {}", seconds_with_link.bold())?;
                     } else {
                         write!(f, " ran in {}s", time.bold())?;
                     }
                 }
+                if let Some(memory) = self.memory() {
+                    write!(f, " using {}", memory.bold())?;
+                }
                 writeln!(f)?;
             }
             Ok(())
@@ -140,15 +168,13 @@ impl Display for SubmissionResponse {
                 self.solved_testcases().to_string().red().bold(),
                 "/".bold(),
                 self.total_testcases().unwrap_or("?").bold(),
-                maybe_add_link(
-                    &self.status.to_string(),
-                    self.submission_id()
-                        .map(|id| format!("https://open.kattis.com/submissions/{id}"))
-                        .as_deref()
-                )
-                .bold()
-                .red()
+                maybe_add_link(&self.status.to_string(), self.submission_link().as_deref())
+                    .bold()
+                    .red()
             )?;
+            if let Some(testcase) = self.failing_testcase() {
+                write!(f, "{}", format!(" on {}", testcase.bold()).red())?;
+            }
             if let Some(time) = self.cpu_time() {
                 write!(
                     f,
@@ -156,7 +182,13 @@ impl Display for SubmissionResponse {
                     format!(" after {}{}", time.bold(), "s".bold()).red()
                 )?;
             }
+            if let Some(memory) = self.memory() {
+                write!(f, "{}", format!(" using {}", memory.bold()).red())?;
+            }
             writeln!(f)?;
+            if let Some(feedback) = self.feedback() {
+                writeln!(f, "{feedback}")?;
+            }
             Ok(())
         }
     }
@@ -166,21 +198,57 @@ fn reset_line() {
     eprint!("\x1B[2K\r");
 }
 
+/// How often and for how long the CLI viewer checks on a submission
+#[derive(Debug, Clone, Copy)]
+pub struct PollOptions {
+    pub interval: Duration,
+    pub timeout: Duration,
+}
+
+/// Follows the submission until it is judged and returns the final status. The submission is
+/// looked up under `submissions_url`, on the same Kattis instance it was submitted to.
 pub async fn view_submission_in_terminal(
     client: Client,
+    submissions_url: &str,
     submission_id: &str,
-) -> anyhow::Result<()> {
+    poll: PollOptions,
+) -> anyhow::Result<String> {
+    let origin = Url::parse(submissions_url)?.origin().ascii_serialization();
+    let submission_link = format!("{submissions_url}/{submission_id}");
     async {
+        let start = Instant::now();
         let mut written_first = false;
         let mut count = 0;
         loop {
-            let response = client
-                .get(format!(
-                    "https://open.kattis.com/submissions/{submission_id}?json"
-                ))
-                .send()
+            if start.elapsed() >= poll.timeout {
+                eprintln!();
+                bail!(
+                    "Gave up waiting for submission {submission_id} to be judged after {}s. \
+                    See {}",
+                    poll.timeout.as_secs(),
+                    display_link(&submission_link)
+                );
+            }
+            count += 1;
+
+            let response = client.get(format!("{submission_link}?json")).send().await?;
+            if response.status() == StatusCode::TOO_MANY_REQUESTS {
+                // Back off for as long as Kattis asks, or a few poll intervals
+                let retry_after = response
+                    .headers()
+                    .get(header::RETRY_AFTER)
+                    .and_then(|value| value.to_str().ok()?.parse().ok())
+                    .map_or(poll.interval * 5, Duration::from_secs);
+                info!("Rate limited by Kattis. Waiting {retry_after:?}");
+                tokio::time::sleep(retry_after).await;
+                continue;
+            }
+            let mut r = response
+                .error_for_status()?
+                .json::<SubmissionResponse>()
                 .await?;
-            let r = response.json::<SubmissionResponse>().await?;
+            r.submissions_url = submissions_url.to_string();
+            r.origin.clone_from(&origin);
 
             if written_first {
                 reset_line();
@@ -191,12 +259,9 @@ pub async fn view_submission_in_terminal(
             eprint!("{r}");
             if r.status.is_terminal() {
                 info!("Queried Kattis {count} times");
-                return Ok(());
+                return Ok(r.status.to_string());
             }
-            // eprintln!("Submission still running. Checking again in 1 second...");
-            // tokio::time::sleep(Duration::from_secs(1)).await;
-            // view_submission_in_terminal(client, submission_id).await
-            count += 1;
+            tokio::time::sleep(poll.interval).await;
         }
     }
     .await
@@ -343,3 +408,50 @@ impl TypedValueParser for SubmissionViewerParser {
         ))
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::submit::viewer::{SubmissionResponse, SubmissionStatus};
+
+    #[test]
+    fn test_failing_testcase() {
+        let response = SubmissionResponse {
+            status: SubmissionStatus::WrongAnswer,
+            testcase_index: 2,
+            testdata_groups_html:
+                r#"<div class="testcase"><i class="is-accepted" title="Test case 1/4: Accepted"></i>
+                <i class="is-accepted" title="Test case 2/4: Accepted"></i>
+                <i class="is-empty" title="Test case 3/4: not checked"></i>
+                <i class="is-rejected" title="Test case 4/4: Wrong Answer"></i></div>"#
+                    .to_string(),
+            feedback_html: "<p>Expected <b>7</b></p>".to_string(),
+            row_html: String::new(),
+            submissions_url: String::new(),
+            origin: String::new(),
+        };
+        assert_eq!(response.failing_testcase(), Some("Test case 4/4"));
+        assert_eq!(response.feedback().as_deref(), Some("Expected 7"));
+    }
+
+    #[test]
+    fn test_row_html() {
+        let response = SubmissionResponse {
+            status: SubmissionStatus::Accepted,
+            testcase_index: 4,
+            testdata_groups_html: String::new(),
+            feedback_html: String::new(),
+            row_html:
+                r#"<tr data-submission-id="123"><td data-type="cpu" class="runtime">0.02&nbsp;s</td>
+                <td data-type="mem" class="memory">4&nbsp;MB</td>"#
+                    .to_string(),
+            submissions_url: "https://liu.kattis.com/submissions".to_string(),
+            origin: "https://liu.kattis.com".to_string(),
+        };
+        assert_eq!(
+            response.submission_link().as_deref(),
+            Some("https://liu.kattis.com/submissions/123")
+        );
+        assert_eq!(response.cpu_time(), Some("0.02"));
+        assert_eq!(response.memory().as_deref(), Some("4 MB"));
+    }
+}